@@ -0,0 +1,32 @@
+use std::time::{Duration, Instant};
+use crate::lexicon::Lexicon;
+use crate::{sum_line_part_two, sum_line_part_two_by_search};
+
+const ROUNDS: u32 = 20;
+
+// Runs both part two implementations over the same lines and reports how long
+// each took. Panics if they ever disagree on a line.
+pub fn compare(lines: &[String], lexicon: &Lexicon) {
+    for line in lines {
+        assert_eq!(
+            sum_line_part_two_by_search(line, lexicon),
+            sum_line_part_two(line, lexicon).unwrap_or(0),
+            "Implementations disagree on line {:?}", line
+        );
+    }
+
+    let search = time(|| lines.iter().map(|l| sum_line_part_two_by_search(l, lexicon)).sum());
+    let scan = time(|| lines.iter().map(|l| sum_line_part_two(l, lexicon).unwrap_or(0)).sum());
+
+    println!("{} lines, best of {} rounds", lines.len(), ROUNDS);
+    println!("find/rfind per word: {:?}", search);
+    println!("single pass scanner: {:?}", scan);
+}
+
+fn time<F: Fn() -> u64>(run: F) -> Duration {
+    (0..ROUNDS).map(|_| {
+        let start = Instant::now();
+        std::hint::black_box(run());
+        start.elapsed()
+    }).min().unwrap_or_default()
+}
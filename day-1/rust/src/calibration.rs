@@ -0,0 +1,127 @@
+use std::fmt;
+use std::str::FromStr;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ErrorKind {
+    NoDigit,
+    Overflow
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CalibrationError {
+    pub line: usize,
+    pub content: String,
+    pub kind: ErrorKind
+}
+
+impl fmt::Display for CalibrationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.kind {
+            ErrorKind::NoDigit => write!(f, "Line {} has no digit or word: {:?}", self.line, self.content),
+            ErrorKind::Overflow => write!(f, "Line {} overflows the calibration value: {:?}", self.line, self.content)
+        }
+    }
+}
+
+// What to do with a line that has no digit token. Overflows are always
+// reported, whatever the policy.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Policy {
+    Strict,
+    Skip,
+    Zero
+}
+
+impl FromStr for Policy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "strict" => Ok(Policy::Strict),
+            "skip" => Ok(Policy::Skip),
+            "zero" => Ok(Policy::Zero),
+            _ => Err(format!("Unknown policy {:?}, expected strict, skip or zero", s))
+        }
+    }
+}
+
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Tally {
+    pub total: u64,
+    pub counted: usize,
+    pub skipped: usize,
+    pub zeroed: usize
+}
+
+impl Tally {
+    pub fn add(&mut self, policy: Policy, line: usize, content: &str, value: Result<u64, ErrorKind>) -> Result<(), CalibrationError> {
+        match (value, policy) {
+            (Ok(v), _) => {
+                self.total = self.total.checked_add(v)
+                    .ok_or_else(|| error(line, content, ErrorKind::Overflow))?;
+                self.counted += 1;
+            },
+            (Err(ErrorKind::NoDigit), Policy::Skip) => self.skipped += 1,
+            (Err(ErrorKind::NoDigit), Policy::Zero) => {
+                self.counted += 1;
+                self.zeroed += 1;
+            },
+            (Err(kind), _) => return Err(error(line, content, kind))
+        }
+        Ok(())
+    }
+}
+
+// Joins the first and last values as decimal text, so "twelve" followed by
+// "3" gives 123.
+pub fn concat_values(first: u32, last: u32) -> Option<u64> {
+    let shift = 10u64.checked_pow(last.checked_ilog10().unwrap_or(0) + 1)?;
+    u64::from(first).checked_mul(shift)?.checked_add(u64::from(last))
+}
+
+fn error(line: usize, content: &str, kind: ErrorKind) -> CalibrationError {
+    CalibrationError { line, content: String::from(content), kind }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_concat_values() {
+        assert_eq!(Some(12), concat_values(1, 2));
+        assert_eq!(Some(90), concat_values(9, 0));
+        assert_eq!(Some(1012), concat_values(10, 12));
+        assert_eq!(None, concat_values(u32::MAX, u32::MAX));
+    }
+
+    #[test]
+    fn test_strict() {
+        let mut tally = Tally::default();
+        assert_eq!(Ok(()), tally.add(Policy::Strict, 1, "a1", Ok(11)));
+        assert_eq!(
+            Err(CalibrationError { line: 2, content: String::from("aa"), kind: ErrorKind::NoDigit }),
+            tally.add(Policy::Strict, 2, "aa", Err(ErrorKind::NoDigit))
+        );
+    }
+
+    #[test]
+    fn test_lenient() {
+        let mut skip = Tally::default();
+        let mut zero = Tally::default();
+        for (i, value) in [Ok(12), Err(ErrorKind::NoDigit), Ok(30), Err(ErrorKind::NoDigit)].iter().enumerate() {
+            skip.add(Policy::Skip, i + 1, "", *value).expect("Expected no error");
+            zero.add(Policy::Zero, i + 1, "", *value).expect("Expected no error");
+        }
+        assert_eq!(Tally { total: 42, counted: 2, skipped: 2, zeroed: 0 }, skip);
+        assert_eq!(Tally { total: 42, counted: 4, skipped: 0, zeroed: 2 }, zero);
+    }
+
+    #[test]
+    fn test_overflow_always_errors() {
+        let mut tally = Tally::default();
+        assert!(tally.add(Policy::Zero, 1, "", Err(ErrorKind::Overflow)).is_err());
+        tally.add(Policy::Zero, 2, "", Ok(u64::MAX)).expect("Expected no error");
+        assert!(tally.add(Policy::Zero, 3, "", Ok(1)).is_err());
+    }
+}
@@ -0,0 +1,193 @@
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use crate::calibration::Policy;
+use crate::extractor::{Extractor, Selection};
+use crate::lexicon::Lexicon;
+use crate::pipeline;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Mode {
+    PartOne,
+    PartTwo,
+    Both
+}
+
+impl FromStr for Mode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "1" | "part-one" | "digits" => Ok(Mode::PartOne),
+            "2" | "part-two" | "words" => Ok(Mode::PartTwo),
+            "both" => Ok(Mode::Both),
+            _ => Err(format!("Unknown mode {:?}, expected part-one, part-two or both", s))
+        }
+    }
+}
+
+impl Mode {
+    pub fn part_one(&self) -> bool {
+        *self != Mode::PartTwo
+    }
+
+    pub fn part_two(&self) -> bool {
+        *self != Mode::PartOne
+    }
+}
+
+pub struct Options {
+    pub mode: Mode,
+    pub policy: Policy,
+    pub bench: bool,
+    pub lexicon: Lexicon,
+    // Used to trace part one, which only ever looks at digits
+    pub digits: Lexicon,
+    pub trace: Option<PathBuf>,
+    // `-` reads from stdin, `None` falls back to input.txt
+    pub input: Option<PathBuf>,
+    pub pipeline: pipeline::Config,
+    // Replaces the usual first and last digit value when set
+    pub extractor: Option<Extractor>
+}
+
+// Usage: day-1 [bench] [--mode part-one|part-two|both] [--policy strict|skip|zero]
+//              [--lexicon NAME|FILE] [--word TOKEN=VALUE]... [--unicode-digits]
+//              [--trace FILE.csv|FILE.json] [--input FILE|-] [--threads N] [--chunk-size N]
+//              [--extract K|all] [--base B]
+pub fn parse(args: &[String]) -> Result<Options, String> {
+    let mut mode = Mode::PartTwo;
+    let mut policy = Policy::Zero;
+    let mut bench = false;
+    let mut lexicon: Option<Lexicon> = None;
+    let mut words: Vec<(String, u32)> = Vec::new();
+    let mut trace: Option<PathBuf> = None;
+    let mut unicode_digits = false;
+    let mut input: Option<PathBuf> = None;
+    let mut pipeline = pipeline::Config::default();
+    let mut selection: Option<Selection> = None;
+    let mut base: Option<u32> = None;
+
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "bench" => bench = true,
+            "--unicode-digits" => unicode_digits = true,
+            "--mode" => mode = Mode::from_str(value_of(arg, iter.next())?)?,
+            "--policy" => policy = Policy::from_str(value_of(arg, iter.next())?)?,
+            "--input" => input = Some(PathBuf::from(value_of(arg, iter.next())?)),
+            "--threads" => {
+                pipeline.threads = count_of(arg, iter.next())?;
+                pipeline.max_in_flight = pipeline.threads * 4;
+            },
+            "--chunk-size" => pipeline.chunk_size = count_of(arg, iter.next())?,
+            "--extract" => selection = Some(Selection::from_str(value_of(arg, iter.next())?)?),
+            "--base" => {
+                let value = value_of(arg, iter.next())?;
+                base = Some(u32::from_str(value).map_err(|_| format!("Expected a number after --base, found {:?}", value))?);
+            },
+            "--trace" => trace = Some(PathBuf::from(value_of(arg, iter.next())?)),
+            "--lexicon" => lexicon = Some(load_lexicon(value_of(arg, iter.next())?)?),
+            "--word" => {
+                let pair = value_of(arg, iter.next())?;
+                let (word, value) = pair.split_once('=')
+                    .ok_or(format!("Expected TOKEN=VALUE after --word, found {:?}", pair))?;
+                let value = u32::from_str(value)
+                    .map_err(|_| format!("Expected a numeric value for --word, found {:?}", value))?;
+                words.push((String::from(word), value));
+            },
+            _ => return Err(format!("Unknown argument {:?}", arg))
+        }
+    }
+
+    let mut lexicon = words.iter().try_fold(lexicon.unwrap_or_else(Lexicon::english), |l, (word, value)| {
+        l.with_word(word, *value).map_err(|e| format!("--word {}={}, {}", word, value, e))
+    })?;
+    let mut digits = Lexicon::digits();
+    if unicode_digits {
+        lexicon = lexicon.with_unicode_digits();
+        digits = digits.with_unicode_digits();
+    }
+    let extractor = match (selection, base) {
+        (None, None) => None,
+        (selection, base) => {
+            let default = Extractor::default();
+            Some(Extractor::new(selection.unwrap_or(default.selection), base.unwrap_or(default.base))?)
+        }
+    };
+    Ok(Options { mode, policy, bench, lexicon, digits, trace, input, pipeline, extractor })
+}
+
+fn value_of<'a>(flag: &str, value: Option<&'a String>) -> Result<&'a str, String> {
+    value.map(|v| v.as_str()).ok_or(format!("Expected a value after {}", flag))
+}
+
+fn count_of(flag: &str, value: Option<&String>) -> Result<usize, String> {
+    let value = value_of(flag, value)?;
+    match usize::from_str(value) {
+        Ok(n) if n > 0 => Ok(n),
+        _ => Err(format!("Expected a positive number after {}, found {:?}", flag, value))
+    }
+}
+
+// Either a built in name (digits, english, german, french) or a lexicon file.
+fn load_lexicon(name: &str) -> Result<Lexicon, String> {
+    match Lexicon::by_name(name) {
+        Some(lexicon) => Ok(lexicon),
+        None => Lexicon::from_file(Path::new(name)).map_err(|e| e.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(s: &str) -> Vec<String> {
+        s.split_whitespace().map(String::from).collect()
+    }
+
+    #[test]
+    fn test_defaults() {
+        let options = parse(&[]).expect("Expected options");
+        assert_eq!(Mode::PartTwo, options.mode);
+        assert_eq!(Policy::Zero, options.policy);
+        assert!(!options.bench);
+        assert_eq!(None, options.trace);
+        assert!(!options.lexicon.unicode_digits());
+        assert_eq!(None, options.input);
+        assert_eq!(None, options.extractor);
+        assert_eq!(9, options.lexicon.words().len());
+    }
+
+    #[test]
+    fn test_parse_options() {
+        let options = parse(&args("--mode both --lexicon german --word null=0")).expect("Expected options");
+        assert_eq!(Mode::Both, options.mode);
+        assert_eq!(10, options.lexicon.words().len());
+        assert_eq!(Mode::PartOne, parse(&args("--mode 1")).expect("Expected options").mode);
+        assert_eq!(Policy::Strict, parse(&args("--policy strict")).expect("Expected options").policy);
+        assert!(parse(&args("--unicode-digits")).expect("Expected options").digits.unicode_digits());
+        let options = parse(&args("--input - --threads 3 --chunk-size 100")).expect("Expected options");
+        assert_eq!(Some(PathBuf::from("-")), options.input);
+        assert_eq!(pipeline::Config { threads: 3, chunk_size: 100, max_in_flight: 12 }, options.pipeline);
+        let options = parse(&args("--extract all --base 16")).expect("Expected options");
+        assert_eq!(Some(Extractor { selection: Selection::All, base: 16 }), options.extractor);
+        let options = parse(&args("--base 2")).expect("Expected options");
+        assert_eq!(Some(Extractor { selection: Selection::Ends(1), base: 2 }), options.extractor);
+        assert_eq!(Some(PathBuf::from("out.json")), parse(&args("--trace out.json")).expect("Expected options").trace);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(parse(&args("--mode three")).is_err());
+        assert!(parse(&args("--policy lax")).is_err());
+        assert!(parse(&args("--mode")).is_err());
+        assert!(parse(&args("--word ten")).is_err());
+        assert_eq!(Some(String::from("--word =5, the token is empty")), parse(&args("--word =5")).err());
+        assert_eq!(Some(String::from("--word t3n=10, \"t3n\" contains a digit")), parse(&args("--word t3n=10")).err());
+        assert!(parse(&args("--frobnicate")).is_err());
+        assert!(parse(&args("--threads 0")).is_err());
+        assert!(parse(&args("--chunk-size lots")).is_err());
+        assert!(parse(&args("--extract 0")).is_err());
+        assert!(parse(&args("--base 40")).is_err());
+    }
+}
@@ -0,0 +1,150 @@
+use std::str::FromStr;
+use crate::calibration::ErrorKind;
+use crate::lexicon::Lexicon;
+use crate::scanner::Token;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Selection {
+    // The first K tokens followed by the last K. With K = 1 this is the usual
+    // calibration value, and a lone token is used for both ends.
+    Ends(usize),
+    All
+}
+
+impl FromStr for Selection {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s == "all" {
+            return Ok(Selection::All);
+        }
+        match usize::from_str(s) {
+            Ok(k) if k > 0 => Ok(Selection::Ends(k)),
+            _ => Err(format!("Expected a positive token count or all, found {:?}", s))
+        }
+    }
+}
+
+// Picks tokens out of a line and joins their values into one number in the
+// given base. A value that does not fit in a single digit of the base is
+// written out in full, the same way "twelve" followed by "3" gives 123 in
+// base 10.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Extractor {
+    pub selection: Selection,
+    pub base: u32
+}
+
+impl Default for Extractor {
+    fn default() -> Self {
+        Extractor { selection: Selection::Ends(1), base: 10 }
+    }
+}
+
+impl Extractor {
+    pub fn new(selection: Selection, base: u32) -> Result<Extractor, String> {
+        if !(2..=36).contains(&base) {
+            return Err(format!("Base must be between 2 and 36, found {}", base));
+        }
+        Ok(Extractor { selection, base })
+    }
+
+    // The selected tokens in line order. Tokens may overlap ("eightwo" has
+    // both eight and two), but only the longest token starting at any one
+    // position is kept.
+    pub fn select(&self, line: &str, lexicon: &Lexicon) -> Vec<Token> {
+        let mut tokens: Vec<Token> = lexicon.tokens(line).collect();
+        tokens.sort_by(|a, b| a.start.cmp(&b.start).then(b.len.cmp(&a.len)));
+        tokens.dedup_by_key(|t| t.start);
+
+        match self.selection {
+            Selection::All => tokens,
+            Selection::Ends(k) => {
+                let mut selected: Vec<Token> = tokens.iter().take(k).copied().collect();
+                selected.extend_from_slice(&tokens[tokens.len().saturating_sub(k)..]);
+                selected
+            }
+        }
+    }
+
+    pub fn extract_u128(&self, line: &str, lexicon: &Lexicon) -> Result<u128, ErrorKind> {
+        let tokens = self.select(line, lexicon);
+        if tokens.is_empty() {
+            return Err(ErrorKind::NoDigit);
+        }
+        tokens.iter().try_fold(0u128, |acc, t| self.append(acc, t.value)).ok_or(ErrorKind::Overflow)
+    }
+
+    pub fn extract_u64(&self, line: &str, lexicon: &Lexicon) -> Result<u64, ErrorKind> {
+        let value = self.extract_u128(line, lexicon)?;
+        u64::try_from(value).map_err(|_| ErrorKind::Overflow)
+    }
+
+    fn append(&self, acc: u128, value: u32) -> Option<u128> {
+        let base = u128::from(self.base);
+        let width = u128::from(value).checked_ilog(base).unwrap_or(0) + 1;
+        acc.checked_mul(base.checked_pow(width)?)?.checked_add(u128::from(value))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn extractor(selection: Selection, base: u32) -> Extractor {
+        Extractor::new(selection, base).expect("Expected a valid extractor")
+    }
+
+    #[test]
+    fn test_default_matches_part_two() {
+        let lexicon = Lexicon::english();
+        let default = Extractor::default();
+        assert_eq!(Ok(29), default.extract_u64("two1nine", &lexicon));
+        assert_eq!(Ok(82), default.extract_u64("eightwo", &lexicon));
+        assert_eq!(Ok(77), default.extract_u64("pqr7st", &lexicon));
+        assert_eq!(Err(ErrorKind::NoDigit), default.extract_u64("abc", &lexicon));
+        let lexicon = lexicon.with_word("eighteen", 18).expect("Expected a word");
+        for line in ["eighteen2", "3eighteen", "eighteenighteen", "oneight"] {
+            let (first, last) = lexicon.first_last(line).expect("Expected a token");
+            assert_eq!(vec!{first, last}, default.select(line, &lexicon), "{}", line);
+        }
+    }
+
+    #[test]
+    fn test_first_and_last_k() {
+        let lexicon = Lexicon::english();
+        assert_eq!(Ok(1256), extractor(Selection::Ends(2), 10).extract_u64("1two3four5six", &lexicon));
+        assert_eq!(Ok(123456), extractor(Selection::Ends(3), 10).extract_u64("1two3four5six", &lexicon));
+        assert_eq!(Ok(1223), extractor(Selection::Ends(2), 10).extract_u64("1two3", &lexicon));
+        assert_eq!(Ok(123456), extractor(Selection::All, 10).extract_u64("1two3four5six", &lexicon));
+    }
+
+    #[test]
+    fn test_other_bases() {
+        let lexicon = Lexicon::english().with_word("ten", 10).expect("Expected a word");
+        assert_eq!(Ok(0b101), extractor(Selection::All, 2).extract_u64("1x0y1", &lexicon));
+        assert_eq!(Ok(0xa1), extractor(Selection::All, 16).extract_u64("ten1", &lexicon));
+        // 5 is written out as 101 in base 2
+        assert_eq!(Ok(0b1101), extractor(Selection::All, 2).extract_u64("1five", &lexicon));
+        assert!(Extractor::new(Selection::All, 1).is_err());
+        assert!(Extractor::new(Selection::All, 37).is_err());
+    }
+
+    #[test]
+    fn test_overflow() {
+        let lexicon = Lexicon::english();
+        let line: String = "9".repeat(25);
+        let all = extractor(Selection::All, 10);
+        assert_eq!(Err(ErrorKind::Overflow), all.extract_u64(&line, &lexicon));
+        assert_eq!(Ok(10u128.pow(25) - 1), all.extract_u128(&line, &lexicon));
+        assert_eq!(Err(ErrorKind::Overflow), all.extract_u128(&"9".repeat(40), &lexicon));
+    }
+
+    #[test]
+    fn test_parse_selection() {
+        assert_eq!(Ok(Selection::All), Selection::from_str("all"));
+        assert_eq!(Ok(Selection::Ends(3)), Selection::from_str("3"));
+        assert!(Selection::from_str("0").is_err());
+        assert!(Selection::from_str("some").is_err());
+    }
+}
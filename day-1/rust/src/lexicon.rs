@@ -0,0 +1,285 @@
+use std::fmt;
+use std::fs::read_to_string;
+use std::path::Path;
+use std::str::FromStr;
+use crate::scanner::{Scanner, Token};
+use crate::unicode::{decimal_value, non_ascii_digits};
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum TokenKind {
+    Digit,
+    Word
+}
+
+// The set of words that count as digit tokens, on top of the ASCII digits
+// which are always recognised. Values are not limited to a single digit, so
+// "ten" or "twelve" can be mapped as well. Decimal digits from other scripts
+// can be switched on with `with_unicode_digits`.
+pub struct Lexicon {
+    words: Vec<(String, u32)>,
+    unicode_digits: bool,
+    tokens: Vec<(String, u32)>,
+    digit_count: usize,
+    scanner: Scanner
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum LexiconError {
+    Io(String),
+    MissingValue { line: usize },
+    InvalidValue { line: usize, value: String },
+    TooManyFields { line: usize },
+    Token { line: usize, error: TokenError }
+}
+
+// Why a word can't be a token. An empty one would match everywhere, and
+// digits would clash with the digit tokens.
+#[derive(Debug, PartialEq, Eq)]
+pub enum TokenError {
+    Empty,
+    Digit(String)
+}
+
+impl fmt::Display for TokenError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TokenError::Empty => write!(f, "the token is empty"),
+            TokenError::Digit(token) => write!(f, "{:?} contains a digit", token)
+        }
+    }
+}
+
+impl fmt::Display for LexiconError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LexiconError::Io(e) => write!(f, "Unable to read lexicon: {}", e),
+            LexiconError::MissingValue { line } => write!(f, "Line {}: expected a token and a value", line),
+            LexiconError::InvalidValue { line, value } => write!(f, "Line {}: {:?} is not a valid value", line, value),
+            LexiconError::TooManyFields { line } => write!(f, "Line {}: expected only a token and a value", line),
+            LexiconError::Token { line, error } => write!(f, "Line {}: {}", line, error)
+        }
+    }
+}
+
+static ENGLISH: [&str; 9] = ["one", "two", "three", "four", "five", "six", "seven", "eight", "nine"];
+static GERMAN: [&str; 9] = ["eins", "zwei", "drei", "vier", "fünf", "sechs", "sieben", "acht", "neun"];
+static FRENCH: [&str; 9] = ["un", "deux", "trois", "quatre", "cinq", "six", "sept", "huit", "neuf"];
+
+fn check_token(token: &str) -> Result<(), TokenError> {
+    if token.is_empty() {
+        return Err(TokenError::Empty);
+    }
+    if token.chars().any(|c| decimal_value(c, true).is_some()) {
+        return Err(TokenError::Digit(String::from(token)));
+    }
+    Ok(())
+}
+
+impl Lexicon {
+    pub fn new(words: Vec<(String, u32)>) -> Result<Lexicon, TokenError> {
+        Lexicon::build(words, false)
+    }
+
+    // Every way of making a lexicon ends up here, so no word skips the checks.
+    fn build(words: Vec<(String, u32)>, unicode_digits: bool) -> Result<Lexicon, TokenError> {
+        for (word, _) in &words {
+            check_token(word)?;
+        }
+        let mut tokens: Vec<(String, u32)> = (0..10u32).map(|d| (d.to_string(), d)).collect();
+        if unicode_digits {
+            tokens.extend(non_ascii_digits().map(|(c, d)| (c.to_string(), d)));
+        }
+        let digit_count = tokens.len();
+        tokens.extend(words.iter().cloned());
+        let scanner = Scanner::new(&tokens.iter().map(|(t, v)| (t.as_str(), *v)).collect::<Vec<_>>());
+        Ok(Lexicon { words, unicode_digits, tokens, digit_count, scanner })
+    }
+
+    // Only the ASCII digits, no words at all.
+    pub fn digits() -> Lexicon {
+        Lexicon::new(Vec::new()).expect("Expected no words to be valid")
+    }
+
+    pub fn english() -> Lexicon {
+        Lexicon::from_words(&ENGLISH)
+    }
+
+    pub fn german() -> Lexicon {
+        Lexicon::from_words(&GERMAN)
+    }
+
+    pub fn french() -> Lexicon {
+        Lexicon::from_words(&FRENCH)
+    }
+
+    pub fn by_name(name: &str) -> Option<Lexicon> {
+        match name {
+            "digits" => Some(Lexicon::digits()),
+            "english" | "en" => Some(Lexicon::english()),
+            "german" | "de" => Some(Lexicon::german()),
+            "french" | "fr" => Some(Lexicon::french()),
+            _ => None
+        }
+    }
+
+    fn from_words(words: &[&str]) -> Lexicon {
+        Lexicon::new(words.iter().enumerate()
+            .map(|(i, w)| (String::from(*w), (i as u32) + 1))
+            .collect()).expect("Expected the built in words to be valid")
+    }
+
+    pub fn with_word(self, word: &str, value: u32) -> Result<Lexicon, TokenError> {
+        let mut words = self.words;
+        words.push((String::from(word), value));
+        Lexicon::build(words, self.unicode_digits)
+    }
+
+    pub fn with_unicode_digits(self) -> Lexicon {
+        Lexicon::build(self.words, true).expect("Expected the words to be checked already")
+    }
+
+    pub fn unicode_digits(&self) -> bool {
+        self.unicode_digits
+    }
+
+    pub fn from_file(path: &Path) -> Result<Lexicon, LexiconError> {
+        let contents = read_to_string(path).map_err(|e| LexiconError::Io(e.to_string()))?;
+        Lexicon::from_str(&contents)
+    }
+
+    pub fn words(&self) -> &[(String, u32)] {
+        &self.words
+    }
+
+    pub fn first_last(&self, line: &str) -> Option<(Token, Token)> {
+        self.scanner.first_last(line)
+    }
+
+    pub fn tokens<'a>(&'a self, line: &'a str) -> impl Iterator<Item = Token> + 'a {
+        self.scanner.tokens(line)
+    }
+
+    pub fn text(&self, token: &Token) -> &str {
+        &self.tokens[token.pattern].0
+    }
+
+    pub fn kind(&self, token: &Token) -> TokenKind {
+        if token.pattern < self.digit_count {
+            TokenKind::Digit
+        } else {
+            TokenKind::Word
+        }
+    }
+}
+
+// One `token value` pair per line. Blank lines and lines starting with `#`
+// are ignored.
+impl FromStr for Lexicon {
+    type Err = LexiconError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut words: Vec<(String, u32)> = Vec::new();
+
+        for (i, raw) in s.lines().enumerate() {
+            let line = i + 1;
+            let trimmed = raw.trim();
+            if trimmed.is_empty() || trimmed.starts_with('#') {
+                continue;
+            }
+            let mut fields = trimmed.split_whitespace();
+            let token = fields.next().ok_or(LexiconError::MissingValue { line })?;
+            let value_str = fields.next().ok_or(LexiconError::MissingValue { line })?;
+            if fields.next().is_some() {
+                return Err(LexiconError::TooManyFields { line });
+            }
+            check_token(token).map_err(|error| LexiconError::Token { line, error })?;
+            let value = u32::from_str(value_str)
+                .map_err(|_| LexiconError::InvalidValue { line, value: String::from(value_str) })?;
+            words.push((String::from(token), value));
+        }
+
+        Ok(Lexicon::new(words).expect("Expected every token to be checked"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn values(lexicon: &Lexicon, line: &str) -> Option<(u32, u32)> {
+        lexicon.first_last(line).map(|(f, l)| (f.value, l.value))
+    }
+
+    #[test]
+    fn test_other_languages() {
+        assert_eq!(Some((1, 3)), values(&Lexicon::german(), "xeinsydrei"));
+        assert_eq!(Some((1, 9)), values(&Lexicon::french(), "unzzneuf"));
+        assert_eq!(None, values(&Lexicon::english(), "einsdrei"));
+    }
+
+    #[test]
+    fn test_token_kinds() {
+        let lexicon = Lexicon::english();
+        let (first, last) = lexicon.first_last("seven4").expect("Expected tokens");
+        assert_eq!(("seven", TokenKind::Word), (lexicon.text(&first), lexicon.kind(&first)));
+        assert_eq!(("4", TokenKind::Digit), (lexicon.text(&last), lexicon.kind(&last)));
+        // Every word maps back to its own text, however many came before it
+        let lexicon = lexicon.with_word("ten", 10).expect("Expected a word").with_unicode_digits();
+        for (word, _) in lexicon.words() {
+            let (first, _) = lexicon.first_last(&format!("a{}3", word)).expect("Expected tokens");
+            assert_eq!((word.as_str(), TokenKind::Word), (lexicon.text(&first), lexicon.kind(&first)));
+        }
+    }
+
+    #[test]
+    fn test_unicode_digits() {
+        let ascii = Lexicon::english();
+        let unicode = Lexicon::english().with_unicode_digits();
+        assert_eq!(Some((4, 4)), values(&ascii, "٣four"));
+        assert_eq!(Some((3, 4)), values(&unicode, "٣four"));
+        assert_eq!(Some((1, 7)), values(&unicode.with_word("eins", 1).expect("Expected a word"), "eins７"));
+
+        let digits = Lexicon::digits().with_unicode_digits();
+        let (first, _) = digits.first_last("x۵").expect("Expected tokens");
+        assert_eq!(TokenKind::Digit, digits.kind(&first));
+        assert_eq!(1, first.start);
+    }
+
+    #[test]
+    fn test_custom_words() {
+        let lexicon = Lexicon::english()
+            .with_word("zero", 0).expect("Expected a word")
+            .with_word("ten", 10).expect("Expected a word")
+            .with_word("twelve", 12).expect("Expected a word");
+        assert_eq!(Some((0, 12)), values(&lexicon, "zeroaaatwelve"));
+        assert_eq!(Some((10, 7)), values(&lexicon, "tenseven"));
+        assert_eq!(Some(TokenError::Empty), Lexicon::english().with_word("", 5).err());
+        assert_eq!(Some(TokenError::Digit(String::from("t3n"))), Lexicon::english().with_word("t3n", 10).err());
+        assert_eq!(Some(TokenError::Empty), Lexicon::new(vec!{(String::new(), 0)}).err());
+    }
+
+    #[test]
+    fn test_parse_lexicon() {
+        let lexicon = Lexicon::from_str("# comment\n\nuno 1\n  dos 2\ndoce 12\n").expect("Expected lexicon");
+        assert_eq!(3, lexicon.words().len());
+        assert_eq!(Some((12, 2)), values(&lexicon, "doce5dos"));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(Err(LexiconError::MissingValue { line: 2 }), Lexicon::from_str("uno 1\ndos").map(|_| ()));
+        assert_eq!(
+            Err(LexiconError::InvalidValue { line: 1, value: String::from("x") }),
+            Lexicon::from_str("uno x").map(|_| ())
+        );
+        assert_eq!(Err(LexiconError::TooManyFields { line: 1 }), Lexicon::from_str("uno 1 2").map(|_| ()));
+        assert_eq!(
+            Err(LexiconError::Token { line: 1, error: TokenError::Digit(String::from("a1")) }),
+            Lexicon::from_str("a1 1").map(|_| ())
+        );
+        assert_eq!(
+            Err(LexiconError::Token { line: 1, error: TokenError::Digit(String::from("a٣")) }),
+            Lexicon::from_str("a٣ 1").map(|_| ())
+        );
+    }
+}
@@ -1,196 +1,519 @@
+use std::env;
+use std::fmt;
 use std::fs::File;
-use threadpool::ThreadPool;
-use std::io::{BufRead, BufReader};
-use std::str::FromStr;
-use std::sync::mpsc::{channel, Sender};
-
-
-static WORDS: [&str; 9] = [
-    "one",
-    "two",
-    "three",
-    "four",
-    "five",
-    "six",
-    "seven",
-    "eight",
-    "nine"
-];
-fn line_sum(line: &String, tx: Sender<u16>) -> () {
-    tx.send(sum_line_part_two(line)).expect("Channel unavailable");
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::process::exit;
+use crate::calibration::{concat_values, CalibrationError, ErrorKind, Policy, Tally};
+use crate::cli::{Mode, Options};
+use crate::lexicon::Lexicon;
+use crate::trace::{trace_line, Format, Trace, TraceWriter};
+use crate::unicode::decimal_value;
+
+mod bench;
+mod calibration;
+mod cli;
+mod extractor;
+mod lexicon;
+mod pipeline;
+mod scanner;
+mod trace;
+mod unicode;
+
+struct LineResult {
+    number: usize,
+    line: String,
+    part_one: Option<Result<u64, ErrorKind>>,
+    part_two: Option<Result<u64, ErrorKind>>,
+    traces: Vec<Trace>
+}
+
+fn line_sum(number: usize, line: String, options: &Options) -> LineResult {
+    let (part_one, part_two) = match &options.extractor {
+        Some(extractor) => (
+            options.mode.part_one().then(|| extractor.extract_u64(&line, &options.digits)),
+            options.mode.part_two().then(|| extractor.extract_u64(&line, &options.lexicon))
+        ),
+        None => (
+            options.mode.part_one().then(|| sum_line(&line, options.lexicon.unicode_digits())),
+            options.mode.part_two().then(|| sum_line_part_two(&line, &options.lexicon))
+        )
+    };
+    let mut traces: Vec<Trace> = Vec::new();
+    if options.trace.is_some() {
+        if part_one.is_some() {
+            traces.push(trace_line("part-one", number, &line, &options.digits, options.extractor.as_ref()));
+        }
+        if part_two.is_some() {
+            traces.push(trace_line("part-two", number, &line, &options.lexicon, options.extractor.as_ref()));
+        }
+    }
+    LineResult { number, line, part_one, part_two, traces }
+}
+
+#[derive(Debug)]
+enum Failure {
+    Io(io::Error),
+    Calibration(CalibrationError)
+}
+
+impl From<io::Error> for Failure {
+    fn from(e: io::Error) -> Self {
+        Failure::Io(e)
+    }
+}
+
+impl From<CalibrationError> for Failure {
+    fn from(e: CalibrationError) -> Self {
+        Failure::Calibration(e)
+    }
+}
+
+impl fmt::Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Failure::Io(e) => write!(f, "Unable to read input: {}", e),
+            Failure::Calibration(e) => write!(f, "{}", e)
+        }
+    }
 }
 
 fn main() {
-    let input_file = File::open("input.txt").expect("File unable to be opened");
-    let reader = BufReader::new(input_file);
+    let args: Vec<String> = env::args().skip(1).collect();
+    let options = match cli::parse(&args) {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("{}", e);
+            exit(2);
+        }
+    };
 
-    let pool: ThreadPool = ThreadPool::new(10);
+    let reader: Box<dyn BufRead + Send> = match &options.input {
+        Some(path) if path.as_os_str() == "-" => Box::new(BufReader::new(io::stdin())),
+        Some(path) => Box::new(BufReader::new(File::open(path).expect("File unable to be opened"))),
+        None => Box::new(BufReader::new(File::open("input.txt").expect("File unable to be opened")))
+    };
+
+    if options.bench {
+        let lines: Vec<String> = reader.lines().map(|l| l.expect("Could not read line")).collect();
+        bench::compare(&lines, &options.lexicon);
+        return;
+    }
 
-    let (tx, rx) = channel();
-    let mut line_count: u32 = 0;
-    reader.lines().for_each(|line: Result<String, std::io::Error>| {
-        line_count += 1;
-        let txc: Sender<u16> = tx.clone();
-        pool.execute(move || { line_sum(&line.unwrap(), txc) });
+    let mut trace_writer = options.trace.as_ref().map(|path| {
+        let out = BufWriter::new(File::create(path).expect("Unable to create trace file"));
+        TraceWriter::new(out, Format::from_path(path)).expect("Unable to write trace file")
     });
-    println!("Waiting for threads to finish...");
-    pool.join();
-    drop(tx);
 
-    println!("Threads Finished!");
-    let mut calibration :u32 = 0;
-    rx.iter().for_each(|num: u16| {
-        calibration += u32::from(num);
+    let report = match calibrate(reader, &options, trace_writer.as_mut()) {
+        Ok(report) => report,
+        Err(e) => {
+            eprintln!("{}", e);
+            exit(1);
+        }
+    };
 
-    });
+    if let (Some(writer), Some(path)) = (trace_writer, &options.trace) {
+        let written = writer.finish().expect("Unable to write trace file");
+        println!("Wrote {} trace records to {}", written, path.display());
+    }
+    report.print(options.mode, options.policy);
+}
+
+#[derive(Debug, Default)]
+struct Report {
+    lines: usize,
+    part_one: Tally,
+    part_two: Tally,
+    differing: usize
+}
+
+impl Report {
+    fn print(&self, mode: Mode, policy: Policy) {
+        match mode {
+            Mode::PartOne => {
+                println!("Calibration Number is {}", self.part_one.total);
+                print_policy_summary(&self.part_one, policy);
+            },
+            Mode::PartTwo => {
+                println!("Calibration Number is {}", self.part_two.total);
+                print_policy_summary(&self.part_two, policy);
+            },
+            Mode::Both => {
+                println!("Part one (digits only) calibration is {}", self.part_one.total);
+                print_policy_summary(&self.part_one, policy);
+                println!("Part two (digits and words) calibration is {}", self.part_two.total);
+                print_policy_summary(&self.part_two, policy);
+                println!("{} of {} lines differ between the two modes", self.differing, self.lines);
+            }
+        }
+    }
+}
 
-    println!("Calibration Number is {}", calibration.to_string());
+// Streams every line of `input` through the pipeline, writing traces and,
+// when comparing both modes, the lines that differ as they come in. Those
+// lines are printed ahead of the totals so they never have to be kept.
+fn calibrate<W: Write>(input: impl BufRead + Send, options: &Options, mut traces: Option<&mut TraceWriter<W>>) -> Result<Report, Failure> {
+    let mut report = Report::default();
+    pipeline::run(input, options.pipeline, |number, line| line_sum(number, line, options), |r: LineResult| {
+        report.lines += 1;
+        if let Some(writer) = traces.as_mut() {
+            for t in &r.traces {
+                writer.write(t)?;
+            }
+        }
+        if let Some(value) = r.part_one {
+            report.part_one.add(options.policy, r.number, &r.line, value)?;
+        }
+        if let Some(value) = r.part_two {
+            report.part_two.add(options.policy, r.number, &r.line, value)?;
+        }
+        if options.mode == Mode::Both && r.part_one != r.part_two {
+            report.differing += 1;
+            println!(
+                "  line {}: {} -> part one {}, part two {}",
+                r.number, r.line, describe(r.part_one), describe(r.part_two)
+            );
+        }
+        Ok::<(), Failure>(())
+    })?;
+    Ok(report)
+}
+
+fn print_policy_summary(tally: &Tally, policy: Policy) {
+    match policy {
+        Policy::Strict => (),
+        Policy::Skip => println!("  {} lines counted, {} lines without a digit skipped", tally.counted, tally.skipped),
+        Policy::Zero => println!("  {} lines counted, {} lines without a digit counted as zero", tally.counted, tally.zeroed)
+    }
+}
+
+fn describe(value: Option<Result<u64, ErrorKind>>) -> String {
+    match value {
+        Some(Ok(v)) => v.to_string(),
+        Some(Err(ErrorKind::NoDigit)) => String::from("no digit"),
+        Some(Err(ErrorKind::Overflow)) => String::from("overflow"),
+        None => String::from("-")
+    }
 }
 
-fn sum_line(line: &String) -> u16 {
-    let mut first_num: char = 'a';
-    let mut last_seen_num: char = 'a';
+fn sum_line(line: &str, unicode_digits: bool) -> Result<u64, ErrorKind> {
+    let mut first_num: Option<u32> = None;
+    let mut last_seen_num: Option<u32> = None;
 
     line.chars().for_each(|f: char| {
-        if f.is_digit(10) {
-            if !first_num.is_digit(10) {
-                first_num = f;
-                last_seen_num = f;
-            } else {
-                last_seen_num = f;
+        if let Some(d) = decimal_value(f, unicode_digits) {
+            if first_num.is_none() {
+                first_num = Some(d);
             }
+            last_seen_num = Some(d);
         }
     });
-    let line_num = first_num.to_string() + &last_seen_num.to_string();
-    u16::from_str(&line_num).unwrap()
+    match first_num.zip(last_seen_num) {
+        Some((first, last)) => concat_values(first, last).ok_or(ErrorKind::Overflow),
+        None => Err(ErrorKind::NoDigit)
+    }
+}
+
+fn sum_line_part_two(line: &str, lexicon: &Lexicon) -> Result<u64, ErrorKind> {
+    match lexicon.first_last(line) {
+        Some((first, last)) => concat_values(first.value, last.value).ok_or(ErrorKind::Overflow),
+        None => Err(ErrorKind::NoDigit)
+    }
 }
 
-fn sum_line_part_two(line: &String) -> u16 {
-    let first_digit = find_first_digit(line);
-    let first_word = find_first_word(line);
+// The original per-word search, kept as the baseline for `cargo run --release -- bench`.
+// Like the scanner, every position here is a byte offset into the line.
+fn sum_line_part_two_by_search(line: &str, lexicon: &Lexicon) -> u64 {
+    let first_digit = find_first_digit(line, lexicon.unicode_digits());
+    let first_word = find_first_word(line, lexicon.words());
 
     let first_value = match (first_digit, first_word) {
-        (Some((fd_pos, fd_char)), Some((fw_pos, fw_char))) => {
+        (Some((fd_pos, fd_value)), Some((fw_pos, fw_value))) => {
             if fd_pos < fw_pos {
-                fd_char
+                fd_value
             } else {
-                fw_char
+                fw_value
             }
         },
-        (Some((_fd_pos, fd_char)), None) => fd_char,
-        (None, Some((_fw_pos, fw_char))) => fw_char,
-        _ => '0'
+        (Some((_fd_pos, fd_value)), None) => fd_value,
+        (None, Some((_fw_pos, fw_value))) => fw_value,
+        _ => return 0
     };
 
-    let last_digit = find_last_digit(line);
-    let last_word = find_last_word(line);
+    let last_digit = find_last_digit(line, lexicon.unicode_digits());
+    let last_word = find_last_word(line, lexicon.words());
 
     let last_value = match (last_digit, last_word) {
-        (Some((ld_pos, ld_char)), Some((lw_pos, lw_char))) => {
+        (Some((ld_pos, ld_value)), Some((lw_pos, lw_value))) => {
             if ld_pos > lw_pos {
-                ld_char
+                ld_value
             } else {
-                lw_char
+                lw_value
             }
         },
-        (Some((_ld_pos, ld_char)), None) => ld_char,
-        (None, Some((_lw_pos, lw_char))) => lw_char,
-        _ => '0'
+        (Some((_ld_pos, ld_value)), None) => ld_value,
+        (None, Some((_lw_pos, lw_value))) => lw_value,
+        _ => return 0
     };
 
-    let line_value = first_value.to_string()+&last_value.to_string();
-    u16::from_str(&line_value).unwrap()
+    concat_values(first_value, last_value).expect("Calibration value overflowed")
 }
 
-fn find_first_digit(line: &String) -> Option<(usize, char)> {
+fn find_first_digit(line: &str, unicode_digits: bool) -> Option<(usize, u32)> {
     let mut found: bool = false;
-    let mut value = 'a';
+    let mut value: u32 = 0;
     let mut pos: usize = usize::MAX;
-    for (i, c) in line.chars().enumerate() {
-        if c.is_digit(10) {
+    for (i, c) in line.char_indices() {
+        if let Some(d) = decimal_value(c, unicode_digits) {
             pos = i;
-            value = c;
+            value = d;
             found = true;
             break;
         }
     };
 
-    return if found {
+    if found {
         Some((pos, value))
     } else {
         None
     }
 }
 
-fn find_first_word(line: &String) -> Option<(usize, char)> {
+fn find_first_word(line: &str, words: &[(String, u32)]) -> Option<(usize, u32)> {
     let mut found: bool = false;
-    let mut value: u8 = 0;
+    let mut value: u32 = 0;
     let mut pos: usize = usize::MAX;
+    let mut len: usize = 0;
 
-    for (i, word) in WORDS.iter().enumerate() {
-        let word_pos = line.find(word);
-        match word_pos {
-            Some(p) => {
-                if p < pos {
-                    value = (i as u8) + 1;
-                    pos = p;
-                    found = true;
-                }
+    // Like the scanner, the longer word wins when two start at the same byte
+    for (word, word_value) in words {
+        let word_pos = line.find(word.as_str());
+        if let Some(p) = word_pos {
+            if p < pos || (p == pos && word.len() > len) {
+                value = *word_value;
+                pos = p;
+                len = word.len();
+                found = true;
             }
-            _ => ()
         }
     }
 
-    return if found {
-        Some((pos, char::from_digit(value as u32, 10)?))
+    if found {
+        Some((pos, value))
     } else {
         None
     }
 }
 
-fn find_last_digit(line: &String) -> Option<(usize, char)> {
+fn find_last_digit(line: &str, unicode_digits: bool) -> Option<(usize, u32)> {
     let mut found: bool = false;
-    let mut value: char = '1';
+    let mut value: u32 = 0;
     let mut pos: usize = usize::MAX;
-    let chars: Vec<char> = line.chars().collect();
-    for (i, c) in chars.iter().enumerate().rev() {
-        if c.is_digit(10) {
+    for (i, c) in line.char_indices().rev() {
+        if let Some(d) = decimal_value(c, unicode_digits) {
             pos = i;
-            value = *c;
+            value = d;
             found = true;
             break;
         }
     };
 
-    return if found {
+    if found {
         Some((pos, value))
     } else {
         None
     }
 }
 
-fn find_last_word(line: &String) -> Option<(usize, char)> {
+fn find_last_word(line: &str, words: &[(String, u32)]) -> Option<(usize, u32)> {
     let mut found: bool = false;
-    let mut value: u8 = 0;
+    let mut value: u32 = 0;
     let mut pos: usize = usize::MIN;
+    let mut len: usize = 0;
 
-    for (i, word) in WORDS.iter().enumerate() {
-        let word_pos = line.rfind(word);
-        match word_pos {
-            Some(p) => {
-                if p > pos {
-                    value = (i as u8) + 1;
-                    pos = p;
-                    found = true;
-                }
+    for (word, word_value) in words {
+        let word_pos = line.rfind(word.as_str());
+        if let Some(p) = word_pos {
+            if !found || p > pos || (p == pos && word.len() > len) {
+                value = *word_value;
+                pos = p;
+                len = word.len();
+                found = true;
             }
-            _ => ()
         }
     }
 
-    return if found {
-        Some((pos, char::from_digit(value as u32, 10)?))
+    if found {
+        Some((pos, value))
     } else {
         None
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn get_example_input() -> Vec<&'static str> {
+        vec!{
+            "two1nine",
+            "eightwothree",
+            "abcone2threexyz",
+            "xtwone3four",
+            "4nineeightseven2",
+            "zoneight234",
+            "7pqrstsixteen",
+        }
+    }
+
+    #[test]
+    fn test_example_part_two() {
+        let lexicon = Lexicon::english();
+        let sum: u64 = get_example_input().iter()
+            .map(|l| sum_line_part_two(l, &lexicon).expect("Expected a value"))
+            .sum();
+        assert_eq!(281, sum);
+    }
+
+    #[test]
+    fn test_example_part_one() {
+        let lines = ["1abc2", "pqr3stu8vwx", "a1b2c3d4e5f", "treb7uchet"];
+        let sum: u64 = lines.iter().map(|l| sum_line(l, false).expect("Expected a value")).sum();
+        assert_eq!(142, sum);
+    }
+
+    #[test]
+    fn test_overlapping_words() {
+        let lexicon = Lexicon::english();
+        assert_eq!(Ok(82), sum_line_part_two("eightwo", &lexicon));
+        assert_eq!(Ok(21), sum_line_part_two("twone", &lexicon));
+    }
+
+    #[test]
+    fn test_scanner_matches_search() {
+        let lexicon = Lexicon::english();
+        for line in get_example_input() {
+            assert_eq!(Ok(sum_line_part_two_by_search(line, &lexicon)), sum_line_part_two(line, &lexicon));
+        }
+    }
+
+    #[test]
+    fn test_prefix_words_match_search() {
+        // "eight" comes first in the list, but "eighteen" starts at the same byte and is longer
+        let lexicon = Lexicon::english().with_word("eighteen", 18).expect("Expected a word");
+        for line in ["eighteen", "xeighteen3", "4eighteenx", "eighteeneight"] {
+            assert_eq!(Ok(sum_line_part_two_by_search(line, &lexicon)), sum_line_part_two(line, &lexicon));
+        }
+        assert_eq!(Ok(1818), sum_line_part_two("eighteen", &lexicon));
+        assert_eq!(Ok(188), sum_line_part_two("eighteeneight", &lexicon));
+    }
+
+    #[test]
+    fn test_multi_digit_words() {
+        let lexicon = Lexicon::english().with_word("twelve", 12).expect("Expected a word").with_word("zero", 0).expect("Expected a word");
+        assert_eq!(Ok(123), sum_line_part_two("twelvexx3", &lexicon));
+        assert_eq!(Ok(90), sum_line_part_two("ninezero", &lexicon));
+        assert_eq!(Ok(1212), sum_line_part_two("twelve", &lexicon));
+    }
+
+    #[test]
+    fn test_mixed_script_positions() {
+        let lexicon = Lexicon::english();
+        // Each é is two bytes, so char and byte positions disagree from here on
+        let lines = ["éééeone1", "1oneéé", "ß2üsixö", "one", "fünfone٣two"];
+        for line in lines {
+            assert_eq!(Ok(sum_line_part_two_by_search(line, &lexicon)), sum_line_part_two(line, &lexicon));
+        }
+        assert_eq!(Ok(11), sum_line_part_two("éééeone1", &lexicon));
+        assert_eq!(Ok(11), sum_line_part_two("one", &lexicon));
+        assert_eq!(Ok(12), sum_line_part_two("fünfone٣two", &lexicon));
+    }
+
+    #[test]
+    fn test_unicode_digits() {
+        let lexicon = Lexicon::english().with_unicode_digits();
+        assert_eq!(Ok(12), sum_line_part_two("fünfone٣two", &lexicon));
+        assert_eq!(Ok(33), sum_line_part_two("fünf٣", &lexicon));
+        assert_eq!(Ok(31), sum_line_part_two("٣xone", &lexicon));
+        assert_eq!(Ok(75), sum_line_part_two("७abc５", &lexicon));
+        assert_eq!(Ok(42), sum_line("a۴b2", true));
+        assert_eq!(Ok(22), sum_line("a۴b2", false));
+        for line in ["éé٣one", "one٣éé", "٣éé１", "x５yz"] {
+            assert_eq!(Ok(sum_line_part_two_by_search(line, &lexicon)), sum_line_part_two(line, &lexicon));
+        }
+    }
+
+    #[test]
+    fn test_no_digit() {
+        assert_eq!(Err(ErrorKind::NoDigit), sum_line("aa", false));
+        assert_eq!(Err(ErrorKind::NoDigit), sum_line_part_two("aa", &Lexicon::english()));
+        assert_eq!(Err(ErrorKind::NoDigit), sum_line("one", false));
+    }
+
+    fn options(args: &str) -> Options {
+        let args: Vec<String> = args.split_whitespace().map(String::from).collect();
+        cli::parse(&args).expect("Expected options")
+    }
+
+    #[test]
+    fn test_strict_report_names_line() {
+        let input = Cursor::new("1abc2\nnothing\n3\n");
+        let error = calibrate::<Vec<u8>>(input, &options("--mode 1 --policy strict"), None)
+            .expect_err("Expected an error");
+        match error {
+            Failure::Calibration(e) => {
+                assert_eq!(2, e.line);
+                assert_eq!("nothing", e.content);
+            },
+            Failure::Io(e) => panic!("Unexpected io error {}", e)
+        }
+
+        let input = Cursor::new("1abc2\nnothing\n3\n");
+        let report = calibrate::<Vec<u8>>(input, &options("--mode 1 --policy skip"), None).expect("Expected a report");
+        assert_eq!((45, 1), (report.part_one.total, report.part_one.skipped));
+    }
+
+    #[test]
+    fn test_calibrate_with_extractor() {
+        let input = Cursor::new("1two3four\nx5y\n");
+        let report = calibrate::<Vec<u8>>(input, &options("--mode both --extract 2"), None).expect("Expected a report");
+        assert_eq!(1313 + 55, report.part_one.total);
+        assert_eq!(1234 + 55, report.part_two.total);
+    }
+
+    #[test]
+    fn test_trace_agrees_with_tally() {
+        let input = Cursor::new("1two3four5six\nfour\n");
+        let mut out: Vec<u8> = Vec::new();
+        let mut writer = TraceWriter::new(&mut out, Format::Csv).expect("Expected to write");
+        let report = calibrate(input, &options("--mode both --extract 2 --base 16 --trace t.csv"), Some(&mut writer))
+            .expect("Expected a report");
+        writer.finish().expect("Expected to write");
+        assert_eq!(0x1335, report.part_one.total);
+        assert_eq!(0x1256 + 0x44, report.part_two.total);
+        let csv = String::from_utf8(out).expect("Expected utf8");
+        let rows: Vec<&str> = csv.lines().collect();
+        assert_eq!("part-one,1,1,0,digit,5,9,digit,1 3 3 5,4917,", rows[1]);
+        assert_eq!("part-two,1,1,0,digit,six,10,word,1 two 5 six,4694,", rows[2]);
+        assert_eq!("part-two,2,four,0,word,four,0,word,four four,68,", rows[4]);
+    }
+
+    #[test]
+    fn test_calibrate_in_order() {
+        let input: String = (0..5000).map(|i| format!("{}x{}\n", i % 10, (i + 1) % 10)).collect();
+        let mut out: Vec<u8> = Vec::new();
+        let mut writer = TraceWriter::new(&mut out, Format::Csv).expect("Expected to write");
+        let report = calibrate(Cursor::new(input), &options("--threads 4 --chunk-size 7 --trace t.csv"), Some(&mut writer))
+            .expect("Expected a report");
+        writer.finish().expect("Expected to write");
+
+        let expected: u64 = (0..5000u64).map(|i| (i % 10) * 10 + (i + 1) % 10).sum();
+        assert_eq!(expected, report.part_two.total);
+        assert_eq!(5000, report.lines);
+        let csv = String::from_utf8(out).expect("Expected utf8");
+        for (i, row) in csv.lines().skip(1).enumerate() {
+            assert!(row.starts_with(&format!("part-two,{},", i + 1)));
+        }
+    }
+}
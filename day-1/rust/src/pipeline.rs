@@ -0,0 +1,205 @@
+use std::any::Any;
+use std::collections::BTreeMap;
+use std::io::{self, BufRead};
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::mpsc::{channel, sync_channel, Receiver};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Config {
+    pub threads: usize,
+    pub chunk_size: usize,
+    // How many chunks may be read but not yet handed to `consume`. Together
+    // with `chunk_size` this bounds how many lines are held in memory.
+    pub max_in_flight: usize
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        let threads = thread::available_parallelism().map(|n| n.get()).unwrap_or(4);
+        Config { threads, chunk_size: 4096, max_in_flight: threads * 4 }
+    }
+}
+
+struct Chunk {
+    index: usize,
+    first_line: usize,
+    lines: Vec<String>
+}
+
+// Reads `input` in chunks of lines, runs `work` on every line across
+// `config.threads` threads and hands the results to `consume` in input order.
+// Idle threads take the next chunk off a shared queue, so a slow chunk never
+// holds up the others. Line numbers start at 1.
+//
+// If `consume` returns an error, reading stops and that error is returned.
+// If `work` panics, everything stops the same way and the panic carries on
+// out of `run`.
+pub fn run<R, E, F, C>(input: impl BufRead + Send, config: Config, work: F, mut consume: C) -> Result<(), E>
+    where R: Send,
+          E: From<io::Error>,
+          F: Fn(usize, String) -> R + Sync,
+          C: FnMut(R) -> Result<(), E> {
+    let threads = config.threads.max(1);
+    let chunk_size = config.chunk_size.max(1);
+    let max_in_flight = config.max_in_flight.max(threads);
+
+    let (chunk_tx, chunk_rx) = sync_channel::<Chunk>(threads);
+    let chunk_rx: Arc<Mutex<Receiver<Chunk>>> = Arc::new(Mutex::new(chunk_rx));
+    // A chunk whose `work` panicked comes back with the panic instead
+    let (result_tx, result_rx) = channel::<(usize, thread::Result<Vec<R>>)>();
+    // The reader takes a permit for every chunk and `consume` gives it back
+    // once the chunk has been handed over in order.
+    let (permit_tx, permit_rx) = sync_channel::<()>(max_in_flight);
+
+    thread::scope(|scope| {
+        let reader = scope.spawn(move || -> io::Result<()> {
+            let mut lines = input.lines();
+            let mut index = 0usize;
+            let mut first_line = 1usize;
+            loop {
+                let mut chunk: Vec<String> = Vec::with_capacity(chunk_size);
+                for line in lines.by_ref().take(chunk_size) {
+                    chunk.push(line?);
+                }
+                if chunk.is_empty() {
+                    return Ok(());
+                }
+                if permit_tx.send(()).is_err() {
+                    return Ok(());
+                }
+                let count = chunk.len();
+                if chunk_tx.send(Chunk { index, first_line, lines: chunk }).is_err() {
+                    return Ok(());
+                }
+                index += 1;
+                first_line += count;
+            }
+        });
+
+        for _ in 0..threads {
+            let chunk_rx = Arc::clone(&chunk_rx);
+            let result_tx = result_tx.clone();
+            let work = &work;
+            scope.spawn(move || loop {
+                let next = chunk_rx.lock().expect("Chunk queue poisoned").recv();
+                let chunk = match next {
+                    Ok(chunk) => chunk,
+                    Err(_) => return
+                };
+                let results = panic::catch_unwind(AssertUnwindSafe(|| {
+                    chunk.lines.into_iter().enumerate()
+                        .map(|(i, line)| work(chunk.first_line + i, line))
+                        .collect::<Vec<R>>()
+                }));
+                if result_tx.send((chunk.index, results)).is_err() {
+                    return;
+                }
+            });
+        }
+        drop(chunk_rx);
+        drop(result_tx);
+
+        let mut panicked: Option<Box<dyn Any + Send>> = None;
+        let consumed = (|| -> Result<(), E> {
+            let mut pending: BTreeMap<usize, Vec<R>> = BTreeMap::new();
+            let mut next = 0usize;
+            for (index, results) in result_rx.iter() {
+                match results {
+                    Ok(results) => pending.insert(index, results),
+                    Err(payload) => {
+                        panicked = Some(payload);
+                        return Ok(());
+                    }
+                };
+                while let Some(results) = pending.remove(&next) {
+                    for r in results {
+                        consume(r)?;
+                    }
+                    next += 1;
+                    permit_rx.recv().expect("Reader holds a permit for every chunk");
+                }
+            }
+            Ok(())
+        })();
+        // Dropping the receivers unblocks the reader and the workers if we
+        // stopped early.
+        drop(permit_rx);
+        drop(result_rx);
+
+        let read = reader.join().expect("Reader thread panicked");
+        if let Some(payload) = panicked {
+            panic::resume_unwind(payload);
+        }
+        consumed?;
+        read.map_err(E::from)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn small() -> Config {
+        Config { threads: 3, chunk_size: 2, max_in_flight: 3 }
+    }
+
+    #[test]
+    fn test_results_in_order() {
+        let input: String = (0..1000).map(|i| format!("{}\n", i)).collect();
+        let mut seen: Vec<(usize, String)> = Vec::new();
+        let result: io::Result<()> = run(Cursor::new(input), small(), |n, line| (n, line), |r| {
+            seen.push(r);
+            Ok(())
+        });
+        result.expect("Expected to finish");
+        assert_eq!(1000, seen.len());
+        for (i, (n, line)) in seen.iter().enumerate() {
+            assert_eq!(i + 1, *n);
+            assert_eq!(i.to_string(), *line);
+        }
+    }
+
+    #[test]
+    fn test_stops_on_error() {
+        let input: String = (0..10000).map(|i| format!("{}\n", i)).collect();
+        let mut count = 0;
+        let result: Result<(), io::Error> = run(Cursor::new(input), small(), |n, _| n, |n| {
+            count += 1;
+            if n == 50 { Err(io::Error::other("stop")) } else { Ok(()) }
+        });
+        assert!(result.is_err());
+        assert_eq!(50, count);
+    }
+
+    #[test]
+    fn test_panics_in_work_are_passed_on() {
+        // Far more chunks than permits, so a lost chunk would leave the reader waiting
+        let input: String = (0..10000).map(|i| format!("{}\n", i)).collect();
+        let result = panic::catch_unwind(|| {
+            let result: io::Result<()> = run(Cursor::new(input), small(), |n, _| if n == 5 { panic!("Bad line") } else { n }, |_| Ok(()));
+            result
+        });
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_empty_input() {
+        let mut count = 0;
+        let result: io::Result<()> = run(Cursor::new(""), Config::default(), |n, _| n, |_| {
+            count += 1;
+            Ok(())
+        });
+        result.expect("Expected to finish");
+        assert_eq!(0, count);
+    }
+
+    #[test]
+    fn test_invalid_utf8_is_an_error() {
+        let input: Vec<u8> = vec!{b'1', b'\n', 0xff, b'\n'};
+        let result: io::Result<()> = run(Cursor::new(input), small(), |n, _| n, |_| Ok(()));
+        assert!(result.is_err());
+    }
+}
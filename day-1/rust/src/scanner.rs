@@ -0,0 +1,158 @@
+use std::collections::VecDeque;
+
+// Multi-pattern matcher (Aho-Corasick) compiled down to a byte level DFA, so a
+// line is scanned once no matter how many tokens we are looking for.
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Token {
+    pub start: usize,
+    pub len: usize,
+    pub value: u32,
+    // Index of the pattern in the list the scanner was built from
+    pub pattern: usize
+}
+
+#[derive(Debug, Copy, Clone)]
+struct Pattern {
+    len: usize,
+    value: u32
+}
+
+pub struct Scanner {
+    transitions: Vec<[usize; 256]>,
+    outputs: Vec<Vec<usize>>,
+    patterns: Vec<Pattern>
+}
+
+impl Scanner {
+    // Tokens have to be non-empty. Each one keeps its index in `tokens` as
+    // its pattern, so callers can look it up again.
+    pub fn new(tokens: &[(&str, u32)]) -> Scanner {
+        let mut transitions: Vec<[usize; 256]> = vec![[0usize; 256]];
+        let mut present: Vec<[bool; 256]> = vec![[false; 256]];
+        let mut outputs: Vec<Vec<usize>> = vec![Vec::new()];
+        let mut patterns: Vec<Pattern> = Vec::new();
+
+        for (text, value) in tokens {
+            assert!(!text.is_empty(), "Expected non-empty tokens");
+            let mut state = 0usize;
+            for b in text.bytes() {
+                let b = b as usize;
+                if !present[state][b] {
+                    transitions.push([0usize; 256]);
+                    present.push([false; 256]);
+                    outputs.push(Vec::new());
+                    transitions[state][b] = transitions.len() - 1;
+                    present[state][b] = true;
+                }
+                state = transitions[state][b];
+            }
+            outputs[state].push(patterns.len());
+            patterns.push(Pattern { len: text.len(), value: *value });
+        }
+
+        // Breadth first pass to fill in the failure transitions so every
+        // state has a direct edge for every byte.
+        let mut fail: Vec<usize> = vec![0usize; transitions.len()];
+        let mut queue: VecDeque<usize> = VecDeque::new();
+        for b in 0..256 {
+            if present[0][b] {
+                queue.push_back(transitions[0][b]);
+            }
+        }
+        while let Some(state) = queue.pop_front() {
+            let inherited = outputs[fail[state]].clone();
+            outputs[state].extend(inherited);
+            for b in 0..256 {
+                if present[state][b] {
+                    let child = transitions[state][b];
+                    fail[child] = transitions[fail[state]][b];
+                    queue.push_back(child);
+                } else {
+                    transitions[state][b] = transitions[fail[state]][b];
+                }
+            }
+        }
+
+        Scanner { transitions, outputs, patterns }
+    }
+
+    // Every match in the line, in the order their last byte is reached.
+    // Overlapping matches are all reported, so "eightwo" yields both words.
+    pub fn tokens<'a>(&'a self, line: &'a str) -> impl Iterator<Item = Token> + 'a {
+        let mut state = 0usize;
+        line.bytes().enumerate().flat_map(move |(i, b)| {
+            state = self.transitions[state][b as usize];
+            self.outputs[state].iter().map(move |&p| {
+                let pattern = self.patterns[p];
+                Token { start: i + 1 - pattern.len, len: pattern.len, value: pattern.value, pattern: p }
+            })
+        })
+    }
+
+    // The earliest and latest starting tokens of the line. When two tokens
+    // start at the same byte the longer one wins.
+    pub fn first_last(&self, line: &str) -> Option<(Token, Token)> {
+        let mut first: Option<Token> = None;
+        let mut last: Option<Token> = None;
+
+        for token in self.tokens(line) {
+            let earlier = match first {
+                Some(f) => token.start < f.start || (token.start == f.start && token.len > f.len),
+                None => true
+            };
+            if earlier {
+                first = Some(token);
+            }
+            let later = match last {
+                Some(l) => token.start > l.start || (token.start == l.start && token.len > l.len),
+                None => true
+            };
+            if later {
+                last = Some(token);
+            }
+        }
+
+        first.zip(last)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn digit_words() -> Scanner {
+        Scanner::new(&[("1", 1), ("2", 2), ("one", 1), ("two", 2), ("eight", 8)])
+    }
+
+    #[test]
+    fn test_overlapping_words() {
+        let scanner = digit_words();
+        let (first, last) = scanner.first_last("eightwo").expect("Expected tokens");
+        assert_eq!(Token { start: 0, len: 5, value: 8, pattern: 4 }, first);
+        assert_eq!(Token { start: 4, len: 3, value: 2, pattern: 3 }, last);
+    }
+
+    #[test]
+    fn test_single_token() {
+        let scanner = digit_words();
+        let (first, last) = scanner.first_last("abc1xyz").expect("Expected tokens");
+        assert_eq!(first, last);
+        assert_eq!(1, first.value);
+    }
+
+    #[test]
+    fn test_no_tokens() {
+        assert_eq!(None, digit_words().first_last("abcdef"));
+    }
+
+    #[test]
+    fn test_nested_patterns() {
+        let scanner = Scanner::new(&[("abcd", 4), ("bc", 2)]);
+        let found: Vec<Token> = scanner.tokens("xabcd").collect();
+        assert_eq!(2, found.len());
+        let (first, last) = scanner.first_last("xabcd").expect("Expected tokens");
+        assert_eq!(4, first.value);
+        assert_eq!(2, last.value);
+    }
+}
@@ -0,0 +1,254 @@
+use std::fmt::Write as _;
+use std::io::{self, Write};
+use std::path::Path;
+use crate::calibration::{concat_values, ErrorKind};
+use crate::extractor::Extractor;
+use crate::lexicon::{Lexicon, TokenKind};
+use crate::scanner::Token;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Format {
+    Csv,
+    Json
+}
+
+impl Format {
+    // JSON for `.json` files, CSV for anything else
+    pub fn from_path(path: &Path) -> Format {
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("json") => Format::Json,
+            _ => Format::Csv
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Found {
+    pub text: String,
+    pub position: usize,
+    pub kind: TokenKind
+}
+
+// Why a single line scored what it did in one of the modes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Trace {
+    pub mode: &'static str,
+    pub line: usize,
+    // Every token the value was built from, in order. Without an extractor
+    // that is the first and the last token.
+    pub tokens: Vec<Found>,
+    pub value: Result<u64, ErrorKind>
+}
+
+impl Trace {
+    pub fn first(&self) -> Option<&Found> {
+        self.tokens.first()
+    }
+
+    pub fn last(&self) -> Option<&Found> {
+        self.tokens.last()
+    }
+}
+
+// Traces the same tokens and value that were added up, so with an extractor
+// that is whatever it selected rather than the first and last token.
+pub fn trace_line(mode: &'static str, number: usize, line: &str, lexicon: &Lexicon, extractor: Option<&Extractor>) -> Trace {
+    let (tokens, value) = match extractor {
+        Some(extractor) => (extractor.select(line, lexicon), extractor.extract_u64(line, lexicon)),
+        None => match lexicon.first_last(line) {
+            Some((first, last)) => (vec!{first, last}, concat_values(first.value, last.value).ok_or(ErrorKind::Overflow)),
+            None => (Vec::new(), Err(ErrorKind::NoDigit))
+        }
+    };
+    let tokens = tokens.iter().map(|t| found(t, lexicon)).collect();
+    Trace { mode, line: number, tokens, value }
+}
+
+fn found(token: &Token, lexicon: &Lexicon) -> Found {
+    Found { text: String::from(lexicon.text(token)), position: token.start, kind: lexicon.kind(token) }
+}
+
+// Writes traces one at a time, so an export never needs every line in memory.
+pub struct TraceWriter<W: Write> {
+    out: W,
+    format: Format,
+    written: usize
+}
+
+impl<W: Write> TraceWriter<W> {
+    pub fn new(mut out: W, format: Format) -> io::Result<TraceWriter<W>> {
+        match format {
+            Format::Csv => writeln!(out, "mode,line,first,first_position,first_kind,last,last_position,last_kind,tokens,value,error")?,
+            Format::Json => write!(out, "[")?
+        }
+        Ok(TraceWriter { out, format, written: 0 })
+    }
+
+    pub fn write(&mut self, trace: &Trace) -> io::Result<()> {
+        match self.format {
+            Format::Csv => writeln!(self.out, "{}", csv_row(trace))?,
+            Format::Json => {
+                let separator = if self.written == 0 { "" } else { "," };
+                write!(self.out, "{}\n  {}", separator, json_object(trace))?
+            }
+        }
+        self.written += 1;
+        Ok(())
+    }
+
+    // Closes off the export and returns how many traces were written.
+    pub fn finish(mut self) -> io::Result<usize> {
+        if self.format == Format::Json {
+            writeln!(self.out, "\n]")?;
+        }
+        self.out.flush()?;
+        Ok(self.written)
+    }
+}
+
+fn kind_name(kind: TokenKind) -> &'static str {
+    match kind {
+        TokenKind::Digit => "digit",
+        TokenKind::Word => "word"
+    }
+}
+
+fn error_name(kind: ErrorKind) -> &'static str {
+    match kind {
+        ErrorKind::NoDigit => "no digit",
+        ErrorKind::Overflow => "overflow"
+    }
+}
+
+fn csv_row(t: &Trace) -> String {
+    let mut row = format!("{},{}", t.mode, t.line);
+    for found in [t.first(), t.last()] {
+        match found {
+            Some(f) => write!(row, ",{},{},{}", csv_field(&f.text), f.position, kind_name(f.kind)),
+            None => write!(row, ",,,")
+        }.expect("Writing to a String cannot fail");
+    }
+    let tokens: Vec<&str> = t.tokens.iter().map(|f| f.text.as_str()).collect();
+    write!(row, ",{}", csv_field(&tokens.join(" "))).expect("Writing to a String cannot fail");
+    match t.value {
+        Ok(v) => write!(row, ",{},", v),
+        Err(e) => write!(row, ",,{}", error_name(e))
+    }.expect("Writing to a String cannot fail");
+    row
+}
+
+fn csv_field(s: &str) -> String {
+    if s.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        String::from(s)
+    }
+}
+
+fn json_object(t: &Trace) -> String {
+    let value = match t.value {
+        Ok(v) => format!("\"value\": {}, \"error\": null", v),
+        Err(e) => format!("\"value\": null, \"error\": {}", json_string(error_name(e)))
+    };
+    let tokens: Vec<String> = t.tokens.iter().map(json_token).collect();
+    format!(
+        "{{\"mode\": {}, \"line\": {}, \"first\": {}, \"last\": {}, \"tokens\": [{}], {}}}",
+        json_string(t.mode), t.line, json_found(t.first()), json_found(t.last()), tokens.join(", "), value
+    )
+}
+
+fn json_found(found: Option<&Found>) -> String {
+    found.map(json_token).unwrap_or(String::from("null"))
+}
+
+fn json_token(f: &Found) -> String {
+    format!("{{\"token\": {}, \"position\": {}, \"kind\": {}}}", json_string(&f.text), f.position, json_string(kind_name(f.kind)))
+}
+
+fn json_string(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len() + 2);
+    escaped.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c if (c as u32) < 0x20 => write!(escaped, "\\u{:04x}", c as u32).expect("Writing to a String cannot fail"),
+            c => escaped.push(c)
+        }
+    }
+    escaped.push('"');
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::extractor::Selection;
+
+    fn example_traces() -> Vec<Trace> {
+        let lexicon = Lexicon::english();
+        vec!{
+            trace_line("part-two", 1, "xtwone3four", &lexicon, None),
+            trace_line("part-two", 2, "nothing", &lexicon, None),
+        }
+    }
+
+    #[test]
+    fn test_trace_line() {
+        let traces = example_traces();
+        assert_eq!(Some(&Found { text: String::from("two"), position: 1, kind: TokenKind::Word }), traces[0].first());
+        assert_eq!(Some(&Found { text: String::from("four"), position: 7, kind: TokenKind::Word }), traces[0].last());
+        assert_eq!(Ok(24), traces[0].value);
+        assert_eq!(Err(ErrorKind::NoDigit), traces[1].value);
+    }
+
+    fn export(traces: &[Trace], format: Format) -> String {
+        let mut out: Vec<u8> = Vec::new();
+        let mut writer = TraceWriter::new(&mut out, format).expect("Expected to write");
+        for t in traces {
+            writer.write(t).expect("Expected to write");
+        }
+        assert_eq!(traces.len(), writer.finish().expect("Expected to write"));
+        String::from_utf8(out).expect("Expected utf8")
+    }
+
+    #[test]
+    fn test_csv() {
+        let csv = export(&example_traces(), Format::Csv);
+        let rows: Vec<&str> = csv.lines().collect();
+        assert_eq!(3, rows.len());
+        assert_eq!("part-two,1,two,1,word,four,7,word,two four,24,", rows[1]);
+        assert_eq!("part-two,2,,,,,,,,,no digit", rows[2]);
+    }
+
+    #[test]
+    fn test_json() {
+        let json = export(&example_traces(), Format::Json);
+        assert!(json.starts_with("[\n  {\"mode\": \"part-two\", \"line\": 1,"));
+        assert!(json.contains("\"first\": {\"token\": \"two\", \"position\": 1, \"kind\": \"word\"}"));
+        assert!(json.contains("},\n  {"));
+        assert!(json.ends_with("\"line\": 2, \"first\": null, \"last\": null, \"tokens\": [], \"value\": null, \"error\": \"no digit\"}\n]\n"));
+        assert_eq!("[\n]\n", export(&[], Format::Json));
+    }
+
+    #[test]
+    fn test_trace_follows_the_extractor() {
+        let lexicon = Lexicon::english();
+        let all = Extractor::new(Selection::All, 10).expect("Expected an extractor");
+        let trace = trace_line("part-two", 1, "1two3four5six", &lexicon, Some(&all));
+        assert_eq!(Ok(123456), trace.value);
+        assert_eq!(vec!{"1", "two", "3", "four", "5", "six"}, trace.tokens.iter().map(|f| f.text.as_str()).collect::<Vec<&str>>());
+        let rows = export(&[trace], Format::Csv);
+        assert!(rows.ends_with("part-two,1,1,0,digit,six,10,word,1 two 3 four 5 six,123456,\n"));
+    }
+
+    #[test]
+    fn test_escaping() {
+        assert_eq!("\"a\\\"b\\\\c\\n\"", json_string("a\"b\\c\n"));
+        assert_eq!("\"a,b\"", csv_field("a,b"));
+        assert_eq!("\"a\"\"b\"", csv_field("a\"b"));
+    }
+}
@@ -0,0 +1,63 @@
+// First code point (the zero) of every run of ten decimal digits (general
+// category Nd) as of Unicode 15. Each run holds the values 0 through 9 in order.
+static DIGIT_ZEROS: [u32; 68] = [
+    0x0030, 0x0660, 0x06F0, 0x07C0, 0x0966, 0x09E6, 0x0A66, 0x0AE6, 0x0B66, 0x0BE6,
+    0x0C66, 0x0CE6, 0x0D66, 0x0DE6, 0x0E50, 0x0ED0, 0x0F20, 0x1040, 0x1090, 0x17E0,
+    0x1810, 0x1946, 0x19D0, 0x1A80, 0x1A90, 0x1B50, 0x1BB0, 0x1C40, 0x1C50, 0xA620,
+    0xA8D0, 0xA900, 0xA9D0, 0xA9F0, 0xAA50, 0xABF0, 0xFF10, 0x104A0, 0x10D30, 0x11066,
+    0x110F0, 0x11136, 0x111D0, 0x112F0, 0x11450, 0x114D0, 0x11650, 0x116C0, 0x11730, 0x118E0,
+    0x11950, 0x11C50, 0x11D50, 0x11DA0, 0x11F50, 0x16A60, 0x16AC0, 0x16B50, 0x1D7CE, 0x1D7D8,
+    0x1D7E2, 0x1D7EC, 0x1D7F6, 0x1E140, 0x1E2F0, 0x1E4F0, 0x1E950, 0x1FBF0
+];
+
+// The value of `c` as a decimal digit. Only ASCII digits count unless
+// `unicode` is set, in which case any script's decimal digits do.
+pub fn decimal_value(c: char, unicode: bool) -> Option<u32> {
+    if !unicode || c.is_ascii() {
+        return c.to_digit(10);
+    }
+    let code = c as u32;
+    let index = DIGIT_ZEROS.partition_point(|&zero| zero <= code);
+    if index == 0 {
+        return None;
+    }
+    let offset = code - DIGIT_ZEROS[index - 1];
+    if offset < 10 { Some(offset) } else { None }
+}
+
+// Every non ASCII decimal digit along with its value.
+pub fn non_ascii_digits() -> impl Iterator<Item = (char, u32)> {
+    DIGIT_ZEROS.iter().skip(1).flat_map(|&zero| {
+        (0..10u32).filter_map(move |d| char::from_u32(zero + d).map(|c| (c, d)))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ascii_only() {
+        assert_eq!(Some(7), decimal_value('7', false));
+        assert_eq!(None, decimal_value('٧', false));
+        assert_eq!(None, decimal_value('a', true));
+    }
+
+    #[test]
+    fn test_other_scripts() {
+        assert_eq!(Some(7), decimal_value('٧', true));
+        assert_eq!(Some(4), decimal_value('۴', true));
+        assert_eq!(Some(3), decimal_value('３', true));
+        assert_eq!(Some(9), decimal_value('९', true));
+        assert_eq!(None, decimal_value('½', true));
+        assert_eq!(None, decimal_value('Ⅻ', true));
+    }
+
+    #[test]
+    fn test_table_is_numeric() {
+        assert!(DIGIT_ZEROS.windows(2).all(|w| w[0] + 10 <= w[1]));
+        for (c, _) in non_ascii_digits() {
+            assert!(c.is_numeric(), "{:?} should be numeric", c);
+        }
+    }
+}
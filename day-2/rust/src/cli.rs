@@ -0,0 +1,247 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use crate::estimate::{Estimator, Prior};
+use crate::game::{Bag, Palette, Round};
+use crate::generate::{Counts, Spec};
+use crate::query::Query;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Mode {
+    Ids,
+    Powers,
+    Both
+}
+
+impl FromStr for Mode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "1" | "part-one" | "ids" => Ok(Mode::Ids),
+            "2" | "part-two" | "powers" => Ok(Mode::Powers),
+            "both" => Ok(Mode::Both),
+            _ => Err(format!("Unknown mode {:?}, expected ids, powers or both", s))
+        }
+    }
+}
+
+impl Mode {
+    pub fn ids(&self) -> bool {
+        *self != Mode::Powers
+    }
+
+    pub fn powers(&self) -> bool {
+        *self != Mode::Ids
+    }
+}
+
+pub struct Options {
+    pub mode: Mode,
+    // The limits a game has to stay within to count towards the ID sum
+    pub bag: Bag,
+    // `None` uses every colour seen in the input
+    pub palette: Option<Palette>,
+    // `-` reads from stdin, `None` falls back to input.txt
+    pub input: Option<PathBuf>,
+    // Also explains why every impossible game breaks the bag limits
+    pub report: bool,
+    // Writes every game's record here, as JSON for `.json` files and CSV otherwise
+    pub records: Option<PathBuf>,
+    // Also lists the games matching each query and what they add up to
+    pub queries: Vec<Query>,
+    // Also estimates what every game's bag held when set
+    pub estimator: Option<Estimator>,
+    // Writes a generated log to `output` instead of solving one when set
+    pub generate: Option<Spec>,
+    pub output: PathBuf
+}
+
+// Usage: day-2 [--mode ids|powers|both] [--bag "12 red, 13 green, 14 blue"] [--bag-file FILE]
+//              [--palette red,green,blue] [--input FILE|-] [--records FILE.csv|FILE.json]
+//              [--report] [--query QUERY]...
+//              [--estimate] [--prior uniform:MAX|poisson:MEAN:MAX] [--credible LEVEL]
+//        day-2 generate [--output FILE] [--seed N] [--games N] [--rounds MIN:MAX]
+//              [--counts uniform:MIN:MAX|poisson:MEAN] [--impossible FRACTION] [--bag ...] [--palette ...]
+pub fn parse(args: &[String]) -> Result<Options, String> {
+    let mut mode = Mode::Powers;
+    let mut bag: Option<Bag> = None;
+    let mut palette: Option<Palette> = None;
+    let mut input: Option<PathBuf> = None;
+    let mut records: Option<PathBuf> = None;
+    let mut report = false;
+    let mut queries: Vec<Query> = Vec::new();
+    let mut estimate = false;
+    let mut prior: Option<Prior> = None;
+    let mut level: Option<f64> = None;
+    let mut generate = false;
+    let mut spec = Spec::default();
+    let mut output = PathBuf::from("generated.txt");
+
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "generate" => generate = true,
+            "--output" => output = PathBuf::from(value_of(arg, iter.next())?),
+            "--seed" => spec.seed = number_of(arg, iter.next())?,
+            "--games" => spec.games = number_of(arg, iter.next())?,
+            "--rounds" => {
+                let value = value_of(arg, iter.next())?;
+                spec.rounds = value.split_once(':')
+                    .and_then(|(min, max)| Some((usize::from_str(min).ok()?, usize::from_str(max).ok()?)))
+                    .ok_or(format!("Expected MIN:MAX after --rounds, found {:?}", value))?;
+            },
+            "--counts" => spec.counts = Counts::from_str(value_of(arg, iter.next())?)?,
+            "--impossible" => {
+                let value = value_of(arg, iter.next())?;
+                spec.impossible = match f64::from_str(value) {
+                    Ok(fraction) if (0.0..=1.0).contains(&fraction) => fraction,
+                    _ => return Err(format!("Expected a fraction between 0 and 1 after --impossible, found {:?}", value))
+                };
+            },
+            "--mode" => mode = Mode::from_str(value_of(arg, iter.next())?)?,
+            "--bag" => bag = Some(Bag::from_str(value_of(arg, iter.next())?).map_err(|e| format!("--bag, {}", e))?),
+            "--bag-file" => bag = Some(load_bag(Path::new(value_of(arg, iter.next())?))?),
+            "--palette" => palette = Some(Palette::from_str(value_of(arg, iter.next())?).map_err(|e| format!("--palette, {}", e))?),
+            "--input" => input = Some(PathBuf::from(value_of(arg, iter.next())?)),
+            "--records" => records = Some(PathBuf::from(value_of(arg, iter.next())?)),
+            "--report" => report = true,
+            "--query" => {
+                let query = value_of(arg, iter.next())?;
+                queries.push(Query::from_str(query).map_err(|e| format!("--query, {}: {}", e, query))?);
+            },
+            "--estimate" => estimate = true,
+            "--prior" => prior = Some(Prior::from_str(value_of(arg, iter.next())?)?),
+            "--credible" => {
+                let value = value_of(arg, iter.next())?;
+                level = Some(f64::from_str(value).map_err(|_| format!("Expected a number after --credible, found {:?}", value))?);
+            },
+            _ => return Err(format!("Unknown argument {:?}", arg))
+        }
+    }
+
+    // The expected sums go beside the log with an `.expected` extension
+    if generate && output.with_extension("expected") == output {
+        return Err(format!("--output {} would be overwritten by its expected sums", output.display()));
+    }
+    let bag = bag.unwrap_or_else(|| Bag::default().with("red", 12).with("green", 13).with("blue", 14));
+    let generate = generate.then(|| Spec { bag: bag.clone(), palette: palette.clone().unwrap_or(spec.palette.clone()), ..spec });
+    // Asking for a prior or a level implies an estimate
+    let estimator = match (estimate, prior, level) {
+        (false, None, None) => None,
+        (_, prior, level) => {
+            let default = Estimator::default();
+            Some(Estimator::new(prior.unwrap_or(default.prior), level.unwrap_or(default.level))?)
+        }
+    };
+    Ok(Options { mode, bag, palette, input, records, report, queries, estimator, generate, output })
+}
+
+fn value_of<'a>(flag: &str, value: Option<&'a String>) -> Result<&'a str, String> {
+    value.map(|v| v.as_str()).ok_or(format!("Expected a value after {}", flag))
+}
+
+fn number_of<T: FromStr>(flag: &str, value: Option<&String>) -> Result<T, String> {
+    let value = value_of(flag, value)?;
+    T::from_str(value).map_err(|_| format!("Expected a number after {}, found {:?}", flag, value))
+}
+
+// Any number of lines like "12 red, 13 green", with `#` starting a comment.
+// A colour listed twice keeps its last count.
+fn load_bag(path: &Path) -> Result<Bag, String> {
+    let content = fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    let mut bag = Bag::default();
+    for (i, line) in content.lines().enumerate() {
+        let line = line.split('#').next().unwrap_or("");
+        if line.trim().is_empty() {
+            continue;
+        }
+        let round = Round::from_str(line).map_err(|e| format!("{} line {}, {}", path.display(), i + 1, e))?;
+        bag = round.pulls.iter().fold(bag, |bag, pull| bag.with(&pull.colour, pull.count));
+    }
+    Ok(bag)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    fn args(s: &str) -> Vec<String> {
+        s.split_whitespace().map(String::from).collect()
+    }
+
+    #[test]
+    fn test_defaults() {
+        let options = parse(&[]).expect("Expected options");
+        assert_eq!(Mode::Powers, options.mode);
+        assert_eq!(Bag::from_str("12 red, 13 green, 14 blue"), Ok(options.bag));
+        assert_eq!(None, options.palette);
+        assert_eq!(None, options.input);
+        assert_eq!(None, options.records);
+        assert!(!options.report);
+        assert_eq!(None, options.estimator);
+        assert_eq!(None, options.generate);
+    }
+
+    #[test]
+    fn test_parse_options() {
+        assert_eq!(Mode::Both, parse(&args("--mode both")).expect("Expected options").mode);
+        assert_eq!(Mode::Ids, parse(&args("--mode 1")).expect("Expected options").mode);
+        let options = parse(&[String::from("--bag"), String::from("1 red, 2 yellow")]).expect("Expected options");
+        assert_eq!(Bag::default().with("red", 1).with("yellow", 2), options.bag);
+        let options = parse(&args("--palette red,yellow --input -")).expect("Expected options");
+        assert_eq!(Palette::from_str("red, yellow").ok(), options.palette);
+        assert_eq!(Some(PathBuf::from("-")), options.input);
+        assert!(parse(&args("--report")).expect("Expected options").report);
+        assert_eq!(Some(PathBuf::from("games.json")), parse(&args("--records games.json")).expect("Expected options").records);
+        let options = parse(&[String::from("--query"), String::from("power > 500"), String::from("--query"), String::from("id < 3")]).expect("Expected options");
+        assert_eq!(2, options.queries.len());
+        assert_eq!(Some(Estimator::default()), parse(&args("--estimate")).expect("Expected options").estimator);
+        let options = parse(&args("--prior poisson:8:30 --credible 0.5")).expect("Expected options");
+        assert_eq!(Some(Estimator { prior: Prior::Poisson { mean: 8.0, max: 30 }, level: 0.5 }), options.estimator);
+    }
+
+    #[test]
+    fn test_generate_options() {
+        let options = parse(&args("generate --seed 9 --games 20 --rounds 2:3 --counts poisson:4 --impossible 0.1 --palette red,blue --output log.txt"))
+            .expect("Expected options");
+        let spec = options.generate.expect("Expected a spec");
+        assert_eq!((9, 20, (2, 3), Counts::Poisson { mean: 4.0 }, 0.1), (spec.seed, spec.games, spec.rounds, spec.counts, spec.impossible));
+        assert_eq!(Palette::from_str("red, blue"), Ok(spec.palette));
+        assert_eq!(options.bag, spec.bag);
+        assert_eq!(PathBuf::from("log.txt"), options.output);
+        assert_eq!(Spec::default(), parse(&args("generate")).expect("Expected options").generate.expect("Expected a spec"));
+    }
+
+    #[test]
+    fn test_bag_file() {
+        let path = env::temp_dir().join(format!("day-2-bag-{}.txt", std::process::id()));
+        fs::write(&path, "# limits\n12 red, 13 green\n\n14 blue # and blue\n").expect("Expected to write");
+        let options = parse(&[String::from("--bag-file"), path.display().to_string()]);
+        fs::write(&path, "12 red\n13\n").expect("Expected to write");
+        let error = parse(&[String::from("--bag-file"), path.display().to_string()]).err();
+        fs::remove_file(&path).expect("Expected to remove");
+
+        assert_eq!(Bag::from_str("12 red, 13 green, 14 blue"), Ok(options.expect("Expected options").bag));
+        assert!(error.expect("Expected an error").contains("line 2, column 3: expected a colour"));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(parse(&args("--mode three")).is_err());
+        assert!(parse(&args("--bag")).is_err());
+        assert!(parse(&args("--bag red")).is_err());
+        assert!(parse(&args("--palette 1")).is_err());
+        assert!(parse(&args("--bag-file /does/not/exist")).is_err());
+        assert!(parse(&args("--frobnicate")).is_err());
+        assert!(parse(&args("--prior uniform")).is_err());
+        assert!(parse(&args("--credible 2")).is_err());
+        assert!(parse(&args("--query red")).is_err());
+        assert!(parse(&args("generate --rounds 3")).is_err());
+        assert!(parse(&args("generate --impossible 1.5")).is_err());
+        assert!(parse(&args("generate --seed -1")).is_err());
+        assert!(parse(&args("generate --output log.expected")).is_err());
+        assert!(parse(&args("generate --output log.expected.txt")).is_ok());
+    }
+}
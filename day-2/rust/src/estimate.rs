@@ -0,0 +1,324 @@
+use std::fmt;
+use std::str::FromStr;
+use crate::game::{Game, Palette};
+
+// Above this many bag compositions a game is not worth estimating.
+const MAX_CELLS: usize = 10_000_000;
+
+// What we believe about the number of cubes of each colour before looking
+// at a game. Colours are assumed independent of each other.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Prior {
+    // Every count from 0 to max is equally likely
+    Uniform { max: u32 },
+    // Poisson with the given mean, cut off above max
+    Poisson { mean: f64, max: u32 }
+}
+
+impl Prior {
+    pub fn max(&self) -> u32 {
+        match *self {
+            Prior::Uniform { max } | Prior::Poisson { max, .. } => max
+        }
+    }
+
+    // Unnormalised, which is all the posterior needs.
+    fn ln_weight(&self, n: u32, ln_factorials: &[f64]) -> f64 {
+        match *self {
+            Prior::Uniform { .. } => 0.0,
+            Prior::Poisson { mean, .. } => f64::from(n) * mean.ln() - ln_factorials[n as usize]
+        }
+    }
+}
+
+// "uniform:MAX" or "poisson:MEAN:MAX"
+impl FromStr for Prior {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let error = || format!("Expected uniform:MAX or poisson:MEAN:MAX, found {:?}", s);
+        let parts: Vec<&str> = s.split(':').collect();
+        match parts.as_slice() {
+            ["uniform", max] => Ok(Prior::Uniform { max: u32::from_str(max).map_err(|_| error())? }),
+            ["poisson", mean, max] => {
+                let mean = f64::from_str(mean).map_err(|_| error())?;
+                if !(mean > 0.0 && mean.is_finite()) {
+                    return Err(format!("The Poisson mean must be positive, found {}", mean));
+                }
+                Ok(Prior::Poisson { mean, max: u32::from_str(max).map_err(|_| error())? })
+            },
+            _ => Err(error())
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ColourEstimate {
+    pub colour: String,
+    pub most_likely: u32,
+    pub mean: f64,
+    // Equal tailed credible interval, both ends included
+    pub lower: u32,
+    pub upper: u32,
+    // Posterior probability of holding exactly `i` cubes of this colour
+    pub probabilities: Vec<f64>
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EstimateError {
+    // A round showed more cubes of a colour than the prior allows
+    BeyondPrior { colour: String, seen: u64, max: u32 },
+    TooManyCompositions(usize)
+}
+
+impl fmt::Display for EstimateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EstimateError::BeyondPrior { colour, seen, max } =>
+                write!(f, "{} {} cubes were seen but the prior allows at most {}", seen, colour, max),
+            EstimateError::TooManyCompositions(cells) =>
+                write!(f, "{} possible bags is too many to estimate, lower the prior maximum", cells)
+        }
+    }
+}
+
+// Estimates what a game's bag held, assuming every round drew its cubes
+// from the whole bag without replacement and put them back afterwards. A
+// round showing k_c cubes of each colour out of K, from a bag of n_c out of
+// N, then has likelihood prod(C(n_c, k_c)) / C(N, K).
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Estimator {
+    pub prior: Prior,
+    // Mass inside the credible interval
+    pub level: f64
+}
+
+impl Default for Estimator {
+    fn default() -> Self {
+        Estimator { prior: Prior::Uniform { max: 40 }, level: 0.9 }
+    }
+}
+
+impl Estimator {
+    pub fn new(prior: Prior, level: f64) -> Result<Estimator, String> {
+        if !(level > 0.0 && level < 1.0) {
+            return Err(format!("The credible level must be between 0 and 1, found {}", level));
+        }
+        Ok(Estimator { prior, level })
+    }
+
+    pub fn estimate(&self, game: &Game, palette: &Palette) -> Result<Vec<ColourEstimate>, EstimateError> {
+        let max = self.prior.max();
+        let colours = palette.colours();
+        // seen[r][c] is how many cubes of colour c round r showed
+        let seen: Vec<Vec<u64>> = game.rounds.iter().map(|round| {
+            colours.iter().map(|colour| {
+                round.pulls.iter().filter(|p| &p.colour == colour).fold(0u64, |sum, p| sum.saturating_add(p.count))
+            }).collect()
+        }).collect();
+
+        // No bag can hold fewer cubes of a colour than one round showed
+        let mut lowest: Vec<u64> = vec!{0; colours.len()};
+        for round in &seen {
+            for (low, &k) in lowest.iter_mut().zip(round) {
+                *low = (*low).max(k);
+            }
+        }
+        if let Some((c, &seen)) = lowest.iter().enumerate().find(|(_, &low)| low > u64::from(max)) {
+            return Err(EstimateError::BeyondPrior { colour: colours[c].clone(), seen, max });
+        }
+        // Everything fits under the prior's maximum from here on
+        let seen: Vec<Vec<u32>> = seen.iter().map(|round| round.iter().map(|&k| k as u32).collect()).collect();
+        let lowest: Vec<u32> = lowest.iter().map(|&low| low as u32).collect();
+        let cells = lowest.iter().try_fold(1usize, |cells, &low| cells.checked_mul((max - low + 1) as usize));
+        // The tables below go up to the biggest bag the prior allows
+        let biggest = (max as usize).checked_mul(colours.len());
+        match (cells, biggest) {
+            (Some(cells), Some(biggest)) if cells <= MAX_CELLS && biggest <= MAX_CELLS => (),
+            _ => return Err(EstimateError::TooManyCompositions(cells.unwrap_or(usize::MAX)))
+        }
+
+        let ln_factorials = ln_factorials(max as usize * colours.len().max(1));
+        let ln_choose = |n: u32, k: u32| {
+            ln_factorials[n as usize] - ln_factorials[k as usize] - ln_factorials[(n - k) as usize]
+        };
+
+        // The log posterior splits into a term for every colour's count and
+        // one for the total, so both are worked out once up front.
+        let by_colour: Vec<Vec<f64>> = (0..colours.len()).map(|c| {
+            (lowest[c]..=max).map(|n| {
+                self.prior.ln_weight(n, &ln_factorials) + seen.iter().map(|round| ln_choose(n, round[c])).sum::<f64>()
+            }).collect()
+        }).collect();
+        let drawn: Vec<u32> = seen.iter().map(|round| round.iter().sum()).collect();
+        let low_total: u32 = lowest.iter().sum();
+        let by_total: Vec<f64> = (low_total..=max * colours.len() as u32).map(|total| {
+            -drawn.iter().map(|&k| ln_choose(total, k)).sum::<f64>()
+        }).collect();
+
+        let ln_posterior = |offsets: &[usize]| {
+            let total: usize = offsets.iter().sum();
+            offsets.iter().enumerate().map(|(c, &i)| by_colour[c][i]).sum::<f64>() + by_total[total]
+        };
+        let mut peak = f64::NEG_INFINITY;
+        for_each_cell(&by_colour, |offsets| peak = peak.max(ln_posterior(offsets)));
+        let mut marginals: Vec<Vec<f64>> = by_colour.iter().map(|counts| vec!{0.0; counts.len()}).collect();
+        for_each_cell(&by_colour, |offsets| {
+            let weight = (ln_posterior(offsets) - peak).exp();
+            for (c, &i) in offsets.iter().enumerate() {
+                marginals[c][i] += weight;
+            }
+        });
+
+        Ok(colours.iter().zip(marginals).zip(&lowest).map(|((colour, weights), &low)| {
+            let mass: f64 = weights.iter().sum();
+            let mut probabilities = vec!{0.0; low as usize};
+            probabilities.extend(weights.iter().map(|w| w / mass));
+            self.summarise(colour, probabilities)
+        }).collect())
+    }
+
+    fn summarise(&self, colour: &str, probabilities: Vec<f64>) -> ColourEstimate {
+        let most_likely = probabilities.iter().enumerate()
+            .fold(0, |best, (n, &p)| if p > probabilities[best] { n } else { best });
+        let mean = probabilities.iter().enumerate().map(|(n, &p)| n as f64 * p).sum();
+        let tail = (1.0 - self.level) / 2.0;
+        let quantile = |q: f64| {
+            let mut cumulative = 0.0;
+            for (n, &p) in probabilities.iter().enumerate() {
+                cumulative += p;
+                // Leave some room for rounding so exact quantiles land on the right count
+                if cumulative >= q - 1e-12 {
+                    return n as u32;
+                }
+            }
+            (probabilities.len() - 1) as u32
+        };
+        ColourEstimate {
+            colour: String::from(colour),
+            most_likely: most_likely as u32,
+            mean,
+            lower: quantile(tail),
+            upper: quantile(1.0 - tail),
+            probabilities
+        }
+    }
+}
+
+fn ln_factorials(n: usize) -> Vec<f64> {
+    let mut table = Vec::with_capacity(n + 1);
+    table.push(0.0);
+    for i in 1..=n {
+        table.push(table[i - 1] + (i as f64).ln());
+    }
+    table
+}
+
+// Calls `f` with every combination of one offset into each of `axes`.
+fn for_each_cell<F: FnMut(&[usize])>(axes: &[Vec<f64>], mut f: F) {
+    if axes.iter().any(|axis| axis.is_empty()) {
+        return;
+    }
+    let mut offsets = vec!{0usize; axes.len()};
+    loop {
+        f(&offsets);
+        let mut axis = 0;
+        loop {
+            if axis == axes.len() {
+                return;
+            }
+            offsets[axis] += 1;
+            if offsets[axis] < axes[axis].len() {
+                break;
+            }
+            offsets[axis] = 0;
+            axis += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(expected: f64, actual: f64) -> bool {
+        (expected - actual).abs() < 1e-9
+    }
+
+    fn estimate(line: &str, prior: Prior, level: f64) -> Result<Vec<ColourEstimate>, EstimateError> {
+        let game = Game::from_str(line).expect("Expected a game");
+        let palette = Palette::from_games(std::slice::from_ref(&game));
+        Estimator::new(prior, level).expect("Expected an estimator").estimate(&game, &palette)
+    }
+
+    #[test]
+    fn test_single_colour_keeps_the_prior() {
+        // Every cube is red, so a round tells us nothing beyond the lower bound
+        let red = &estimate("Game 1: 2 red; 1 red", Prior::Uniform { max: 4 }, 0.5).expect("Expected an estimate")[0];
+        assert_eq!(5, red.probabilities.len());
+        assert!(close(0.0, red.probabilities[1]));
+        assert!(close(1.0 / 3.0, red.probabilities[4]));
+        assert_eq!(2, red.most_likely);
+        assert!(close(3.0, red.mean));
+        assert_eq!((2, 4), (red.lower, red.upper));
+    }
+
+    #[test]
+    fn test_two_colours() {
+        // Bags (1, 1), (1, 2), (2, 1) and (2, 2) explain "1 red, 1 blue" with
+        // likelihood 1, 2/3, 2/3 and 2/3
+        let estimates = estimate("Game 1: 1 red, 1 blue", Prior::Uniform { max: 2 }, 0.9).expect("Expected an estimate");
+        let red = &estimates[0];
+        assert_eq!("red", red.colour);
+        assert!(close(5.0 / 9.0, red.probabilities[1]));
+        assert!(close(4.0 / 9.0, red.probabilities[2]));
+        assert!(close(13.0 / 9.0, red.mean));
+        assert_eq!(1, red.most_likely);
+        assert_eq!((1, 2), (red.lower, red.upper));
+        assert_eq!(red.probabilities, estimates[1].probabilities);
+    }
+
+    #[test]
+    fn test_poisson_prior_pulls_towards_the_mean() {
+        let line = "Game 1: 3 red, 1 blue; 2 red, 2 blue";
+        let uniform = estimate(line, Prior::Uniform { max: 30 }, 0.9).expect("Expected an estimate");
+        let poisson = estimate(line, Prior::Poisson { mean: 4.0, max: 30 }, 0.9).expect("Expected an estimate");
+        assert!(poisson[0].mean < uniform[0].mean);
+        assert!(poisson[0].upper <= uniform[0].upper);
+        for e in uniform.iter().chain(&poisson) {
+            assert!(close(1.0, e.probabilities.iter().sum()));
+            assert!(e.lower <= e.most_likely && e.most_likely <= e.upper);
+        }
+        assert!(poisson[0].lower >= 3);
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(
+            Err(EstimateError::BeyondPrior { colour: String::from("blue"), seen: 6, max: 5 }),
+            estimate("Game 1: 1 red, 6 blue", Prior::Uniform { max: 5 }, 0.9)
+        );
+        assert!(matches!(
+            estimate("Game 1: 1 a, 1 b, 1 c, 1 d, 1 e", Prior::Uniform { max: 255 }, 0.9),
+            Err(EstimateError::TooManyCompositions(_))
+        ));
+        assert!(matches!(
+            estimate("Game 1: 4294967294 red", Prior::Uniform { max: u32::MAX }, 0.9),
+            Err(EstimateError::TooManyCompositions(_))
+        ));
+        assert_eq!(
+            Err(EstimateError::BeyondPrior { colour: String::from("red"), seen: 1 << 40, max: 5 }),
+            estimate("Game 1: 1099511627776 red", Prior::Uniform { max: 5 }, 0.9)
+        );
+        assert!(Estimator::new(Prior::Uniform { max: 5 }, 1.0).is_err());
+    }
+
+    #[test]
+    fn test_parse_prior() {
+        assert_eq!(Ok(Prior::Uniform { max: 20 }), Prior::from_str("uniform:20"));
+        assert_eq!(Ok(Prior::Poisson { mean: 7.5, max: 40 }), Prior::from_str("poisson:7.5:40"));
+        assert!(Prior::from_str("poisson:0:40").is_err());
+        assert!(Prior::from_str("uniform").is_err());
+        assert!(Prior::from_str("normal:1:2").is_err());
+    }
+}
@@ -0,0 +1,554 @@
+use std::cmp::max;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pull {
+    pub count: u64,
+    pub colour: String
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Round {
+    pub pulls: Vec<Pull>
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Game {
+    pub id: u64,
+    pub rounds: Vec<Round>
+}
+
+// The colours a variant is played with, in the order they were listed or first seen.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Palette {
+    colours: Vec<String>
+}
+
+// How many cubes of each colour a bag holds. Colours it does not mention hold none.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Bag {
+    counts: BTreeMap<String, u64>
+}
+
+impl Palette {
+    pub fn from_games(games: &[Game]) -> Palette {
+        let mut palette = Palette { colours: Vec::new() };
+        for pull in games.iter().flat_map(|game| game.pulls()) {
+            if !palette.contains(&pull.colour) {
+                palette.colours.push(pull.colour.clone());
+            }
+        }
+        palette
+    }
+
+    pub fn contains(&self, colour: &str) -> bool {
+        self.colours.iter().any(|c| c == colour)
+    }
+
+    pub fn colours(&self) -> &[String] {
+        &self.colours
+    }
+}
+
+// A comma separated list of colours, e.g. "red, green, blue, yellow".
+impl FromStr for Palette {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parser = Parser::new(s, None);
+        let mut palette = Palette { colours: vec!{parser.colour()?} };
+        while parser.eat(',') {
+            let colour = parser.colour()?;
+            if !palette.contains(&colour) {
+                palette.colours.push(colour);
+            }
+        }
+        parser.end()?;
+        Ok(palette)
+    }
+}
+
+impl Bag {
+    pub fn with(mut self, colour: &str, count: u64) -> Bag {
+        self.counts.insert(String::from(colour), count);
+        self
+    }
+
+    pub fn count(&self, colour: &str) -> u64 {
+        self.counts.get(colour).copied().unwrap_or(0)
+    }
+
+    // The product of the counts of every colour in the palette, or `None` if
+    // it does not fit in a u64.
+    pub fn power(&self, palette: &Palette) -> Option<u64> {
+        palette.colours.iter().try_fold(1u64, |power, colour| power.checked_mul(self.count(colour)))
+    }
+
+    // How many cubes of the palette's colours the bag holds in all.
+    pub fn size(&self, palette: &Palette) -> Option<u64> {
+        palette.colours.iter().try_fold(0u64, |size, colour| size.checked_add(self.count(colour)))
+    }
+}
+
+// Written the same way as a round, e.g. "12 red, 13 green, 14 blue".
+impl FromStr for Bag {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let round = Round::from_str(s)?;
+        Ok(round.pulls.iter().fold(Bag::default(), |bag, pull| bag.with(&pull.colour, pull.count)))
+    }
+}
+
+// A pull that drew more cubes of a colour than the bag holds.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Violation {
+    // Counted from 1, like the rounds in the log
+    pub round: usize,
+    pub colour: String,
+    pub count: u64,
+    pub limit: u64
+}
+
+impl Violation {
+    pub fn excess(&self) -> u64 {
+        self.count - self.limit
+    }
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "round {} drew {} {}, {} more than the {} in the bag", self.round, self.count, self.colour, self.excess(), self.limit)
+    }
+}
+
+// Whether a game fits in a bag, and every pull that does not if it does not.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Verdict {
+    pub violations: Vec<Violation>
+}
+
+impl Verdict {
+    pub fn is_possible(&self) -> bool {
+        self.violations.is_empty()
+    }
+}
+
+impl Round {
+    pub fn is_possible(&self, bag: &Bag) -> bool {
+        self.pulls.iter().all(|pull| pull.count <= bag.count(&pull.colour))
+    }
+}
+
+impl Game {
+    // Like `from_str`, but any colour outside the palette is an error.
+    pub fn parse(s: &str, palette: &Palette) -> Result<Game, ParseError> {
+        let mut parser = Parser::new(s, Some(palette));
+        let game = parser.game()?;
+        parser.end()?;
+        Ok(game)
+    }
+
+    pub fn pulls(&self) -> impl Iterator<Item = &Pull> {
+        self.rounds.iter().flat_map(|round| round.pulls.iter())
+    }
+
+    pub fn is_possible(&self, bag: &Bag) -> bool {
+        self.rounds.iter().all(|round| round.is_possible(bag))
+    }
+
+    // The power of the game's minimal bag.
+    pub fn power(&self, palette: &Palette) -> Result<u64, Overflow> {
+        self.minimal_bag().power(palette).ok_or(Overflow { game: self.id, what: "the power of its minimal bag" })
+    }
+
+    pub fn check(&self, bag: &Bag) -> Verdict {
+        let violations = self.rounds.iter().enumerate().flat_map(|(i, round)| {
+            round.pulls.iter().filter_map(move |pull| {
+                let limit = bag.count(&pull.colour);
+                (pull.count > limit).then(|| Violation { round: i + 1, colour: pull.colour.clone(), count: pull.count, limit })
+            })
+        }).collect();
+        Verdict { violations }
+    }
+
+    // The fewest cubes of each colour the bag could have held.
+    pub fn minimal_bag(&self) -> Bag {
+        let mut bag = Bag::default();
+        for pull in self.pulls() {
+            let count = bag.counts.entry(pull.colour.clone()).or_insert(0);
+            *count = max(pull.count, *count);
+        }
+        bag
+    }
+}
+
+// Some total for a game grew past what a u64 holds.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Overflow {
+    pub game: u64,
+    pub what: &'static str
+}
+
+impl fmt::Display for Overflow {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Game {}: {} does not fit in 64 bits", self.game, self.what)
+    }
+}
+
+// Written back in the same form they are parsed from.
+impl fmt::Display for Pull {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.count, self.colour)
+    }
+}
+
+impl fmt::Display for Round {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let pulls: Vec<String> = self.pulls.iter().map(Pull::to_string).collect();
+        write!(f, "{}", pulls.join(", "))
+    }
+}
+
+impl fmt::Display for Game {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let rounds: Vec<String> = self.rounds.iter().map(Round::to_string).collect();
+        write!(f, "Game {}: {}", self.id, rounds.join("; "))
+    }
+}
+
+impl fmt::Display for Palette {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.colours.join(", "))
+    }
+}
+
+impl fmt::Display for Bag {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let counts: Vec<String> = self.counts.iter().map(|(colour, count)| format!("{} {}", count, colour)).collect();
+        write!(f, "{}", counts.join(", "))
+    }
+}
+
+// Where a line stopped making sense. Columns count characters from 1.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    pub column: usize,
+    pub expected: &'static str,
+    pub found: String
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "column {}: expected {}, found {}", self.column, self.expected, self.found)
+    }
+}
+
+impl FromStr for Game {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parser = Parser::new(s, None);
+        let game = parser.game()?;
+        parser.end()?;
+        Ok(game)
+    }
+}
+
+impl FromStr for Round {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parser = Parser::new(s, None);
+        let round = parser.round()?;
+        parser.end()?;
+        Ok(round)
+    }
+}
+
+impl FromStr for Pull {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parser = Parser::new(s, None);
+        let pull = parser.pull()?;
+        parser.end()?;
+        Ok(pull)
+    }
+}
+
+// Game  = "Game" number ":" Round (";" Round)*
+// Round = Pull ("," Pull)*
+// Pull  = number colour
+struct Parser<'a> {
+    chars: Vec<(usize, char)>,
+    source: &'a str,
+    palette: Option<&'a Palette>,
+    pos: usize
+}
+
+impl<'a> Parser<'a> {
+    fn new(source: &'a str, palette: Option<&'a Palette>) -> Parser<'a> {
+        Parser { chars: source.char_indices().collect(), source, palette, pos: 0 }
+    }
+
+    fn game(&mut self) -> Result<Game, ParseError> {
+        self.skip_spaces();
+        self.keyword("Game", "\"Game\"")?;
+        let id = self.number("a game id")?;
+        self.skip_spaces();
+        self.symbol(':', "':' after the game id")?;
+
+        let mut rounds = vec!{self.round()?};
+        while self.eat(';') {
+            rounds.push(self.round()?);
+        }
+        Ok(Game { id, rounds })
+    }
+
+    fn round(&mut self) -> Result<Round, ParseError> {
+        let mut pulls = vec!{self.pull()?};
+        while self.eat(',') {
+            pulls.push(self.pull()?);
+        }
+        Ok(Round { pulls })
+    }
+
+    fn pull(&mut self) -> Result<Pull, ParseError> {
+        let count = self.number("a cube count")?;
+        let colour = self.colour()?;
+        Ok(Pull { count, colour })
+    }
+
+    fn colour(&mut self) -> Result<String, ParseError> {
+        let column = self.skip_spaces();
+        let colour = self.word();
+        if colour.is_empty() {
+            return Err(self.error_at(column, "a colour"));
+        }
+        match self.palette {
+            Some(palette) if !palette.contains(colour) => Err(self.error_at(column, "a colour from the palette")),
+            _ => Ok(String::from(colour))
+        }
+    }
+
+    fn end(&mut self) -> Result<(), ParseError> {
+        self.skip_spaces();
+        match self.peek() {
+            None => Ok(()),
+            Some(_) => Err(self.error("';', ',' or the end of the line"))
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).map(|(_, c)| *c)
+    }
+
+    fn offset(&self, pos: usize) -> usize {
+        self.chars.get(pos).map(|(i, _)| *i).unwrap_or(self.source.len())
+    }
+
+    // Returns the position of the next non space character.
+    fn skip_spaces(&mut self) -> usize {
+        while self.peek().is_some_and(|c| c.is_whitespace()) {
+            self.pos += 1;
+        }
+        self.pos
+    }
+
+    fn eat(&mut self, symbol: char) -> bool {
+        self.skip_spaces();
+        if self.peek() == Some(symbol) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn symbol(&mut self, symbol: char, expected: &'static str) -> Result<(), ParseError> {
+        if self.eat(symbol) { Ok(()) } else { Err(self.error(expected)) }
+    }
+
+    fn word(&mut self) -> &'a str {
+        let start = self.offset(self.pos);
+        while self.peek().is_some_and(|c| c.is_alphabetic()) {
+            self.pos += 1;
+        }
+        &self.source[start..self.offset(self.pos)]
+    }
+
+    fn keyword(&mut self, keyword: &str, expected: &'static str) -> Result<(), ParseError> {
+        let column = self.pos;
+        if self.word() == keyword { Ok(()) } else { Err(self.error_at(column, expected)) }
+    }
+
+    fn number(&mut self, expected: &'static str) -> Result<u64, ParseError> {
+        let column = self.skip_spaces();
+        let start = self.offset(self.pos);
+        while self.peek().is_some_and(|c| c.is_ascii_digit()) {
+            self.pos += 1;
+        }
+        let digits = &self.source[start..self.offset(self.pos)];
+        if digits.is_empty() {
+            return Err(self.error_at(column, expected));
+        }
+        u64::from_str(digits).map_err(|_| ParseError {
+            column: column + 1,
+            expected: "a number that fits in 64 bits",
+            found: format!("{:?}", digits)
+        })
+    }
+
+    fn error(&self, expected: &'static str) -> ParseError {
+        self.error_at(self.pos, expected)
+    }
+
+    // Reports whatever token starts at `pos`, or the end of the line.
+    fn error_at(&self, pos: usize, expected: &'static str) -> ParseError {
+        let rest = &self.source[self.offset(pos)..];
+        let found = match rest.chars().next() {
+            None => String::from("end of line"),
+            Some(c) if c.is_alphanumeric() => format!("{:?}", rest.chars().take_while(|c| c.is_alphanumeric()).collect::<String>()),
+            Some(c) => format!("{:?}", c.to_string())
+        };
+        ParseError { column: pos + 1, expected, found }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pull(count: u64, colour: &str) -> Pull {
+        Pull { count, colour: String::from(colour) }
+    }
+
+    #[test]
+    fn test_parse_game() {
+        let game = Game::from_str("Game 3: 8 green, 6 blue, 20 red; 5 blue, 4 red").expect("Expected a game");
+        assert_eq!(3, game.id);
+        assert_eq!(2, game.rounds.len());
+        assert_eq!(pull(20, "red"), game.rounds[0].pulls[2]);
+        assert_eq!(Round { pulls: vec!{pull(5, "blue"), pull(4, "red")} }, game.rounds[1]);
+    }
+
+    #[test]
+    fn test_display_round_trips() {
+        let line = "Game 3: 8 green, 6 blue, 20 red; 5 blue, 4 red";
+        assert_eq!(line, Game::from_str(line).expect("Expected a game").to_string());
+        let bag = Bag::from_str("14 blue, 12 red").expect("Expected a bag");
+        assert_eq!(Ok(bag.clone()), Bag::from_str(&bag.to_string()));
+        assert_eq!("red, green", Palette::from_str("red,green").expect("Expected a palette").to_string());
+    }
+
+    #[test]
+    fn test_parse_pull_and_round() {
+        assert_eq!(Ok(pull(7, "green")), Pull::from_str(" 7 green "));
+        assert_eq!(2, Round::from_str("1 red, 2 blue").expect("Expected a round").pulls.len());
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(
+            Err(ParseError { column: 1, expected: "\"Game\"", found: String::from("\"Gmae\"") }),
+            Game::from_str("Gmae 1: 1 red")
+        );
+        assert_eq!(
+            Err(ParseError { column: 8, expected: "':' after the game id", found: String::from("\"1\"") }),
+            Game::from_str("Game 1 1 red")
+        );
+        assert_eq!(
+            Err(ParseError { column: 11, expected: "a colour from the palette", found: String::from("\"purple\"") }),
+            Game::parse("Game 1: 1 purple", &Palette::from_str("red, green, blue").expect("Expected a palette"))
+        );
+        assert_eq!(
+            Err(ParseError { column: 17, expected: "a colour", found: String::from("end of line") }),
+            Game::from_str("Game 1: 1 red, 2")
+        );
+        assert_eq!(
+            Err(ParseError { column: 16, expected: "a cube count", found: String::from("\"blue\"") }),
+            Game::from_str("Game 1: 1 red, blue")
+        );
+        assert_eq!(
+            Err(ParseError { column: 15, expected: "';', ',' or the end of the line", found: String::from("\"|\"") }),
+            Game::from_str("Game 1: 1 red | 2 blue")
+        );
+        assert_eq!(
+            Err(ParseError { column: 9, expected: "a number that fits in 64 bits", found: String::from("\"18446744073709551616\"") }),
+            Game::from_str("Game 1: 18446744073709551616 red")
+        );
+    }
+
+    #[test]
+    fn test_possible_and_minimal_bag() {
+        let max_cubes = Bag::from_str("12 red, 13 green, 14 blue").expect("Expected a bag");
+        let possible = Game::from_str("Game 1: 3 blue, 4 red; 1 red, 2 green, 6 blue; 2 green").expect("Expected a game");
+        let impossible = Game::from_str("Game 3: 8 green, 6 blue, 20 red; 5 blue, 4 red, 13 green").expect("Expected a game");
+
+        assert!(possible.is_possible(&max_cubes));
+        assert!(!impossible.is_possible(&max_cubes));
+        assert_eq!(Bag::default().with("red", 4).with("green", 2).with("blue", 6), possible.minimal_bag());
+        assert_eq!(Ok(48), possible.power(&Palette::from_str("red, green, blue").expect("Expected a palette")));
+    }
+
+    #[test]
+    fn test_check() {
+        let bag = Bag::from_str("12 red, 13 green, 14 blue").expect("Expected a bag");
+        let game = Game::from_str("Game 4: 1 green, 3 red, 6 blue; 3 green, 6 red; 3 green, 15 blue, 14 red, 1 pink").expect("Expected a game");
+        let verdict = game.check(&bag);
+        assert!(!verdict.is_possible());
+        assert_eq!(vec!{
+            Violation { round: 3, colour: String::from("blue"), count: 15, limit: 14 },
+            Violation { round: 3, colour: String::from("red"), count: 14, limit: 12 },
+            Violation { round: 3, colour: String::from("pink"), count: 1, limit: 0 },
+        }, verdict.violations);
+        assert_eq!(2, verdict.violations[1].excess());
+        assert_eq!("round 3 drew 14 red, 2 more than the 12 in the bag", verdict.violations[1].to_string());
+
+        let game = Game::from_str("Game 1: 3 blue, 4 red; 1 red, 2 green, 6 blue").expect("Expected a game");
+        assert_eq!(Verdict { violations: Vec::new() }, game.check(&bag));
+        assert!(game.check(&bag).is_possible());
+    }
+
+    #[test]
+    fn test_wide_counts() {
+        let palette = Palette::from_str("red, green, blue").expect("Expected a palette");
+        let game = Game::from_str("Game 300: 300 red, 50 green; 50 blue").expect("Expected a game");
+        assert_eq!(300, game.id);
+        assert_eq!(Ok(750_000), game.power(&palette));
+        assert_eq!(Some(400), game.minimal_bag().size(&palette));
+
+        let game = Game::from_str("Game 7: 4294967296 red, 4294967296 green, 2 blue").expect("Expected a game");
+        let overflow = game.power(&palette).expect_err("Expected an overflow");
+        assert_eq!(Overflow { game: 7, what: "the power of its minimal bag" }, overflow);
+        assert_eq!("Game 7: the power of its minimal bag does not fit in 64 bits", overflow.to_string());
+        let game = Game::from_str("Game 8: 18446744073709551615 red, 1 green").expect("Expected a game");
+        assert_eq!(None, game.minimal_bag().size(&palette));
+    }
+
+    #[test]
+    fn test_other_palettes() {
+        let games: Vec<Game> = [
+            "Game 1: 3 blue, 4 yellow; 2 violet",
+            "Game 2: 5 yellow, 1 blue; 2 blue",
+        ].iter().map(|l| Game::from_str(l).expect("Expected a game")).collect();
+        let palette = Palette::from_games(&games);
+        assert_eq!(["blue", "yellow", "violet"], palette.colours());
+        assert_eq!(Ok(24), games[0].power(&palette));
+        // Game 2 never shows a violet cube, so its bag may hold none
+        assert_eq!(Ok(0), games[1].power(&palette));
+
+        let bag = Bag::from_str("5 yellow, 3 blue").expect("Expected a bag");
+        assert!(games[1].is_possible(&bag));
+        assert!(!games[0].is_possible(&bag));
+
+        let config = Palette::from_str("blue, yellow, violet, blue").expect("Expected a palette");
+        assert_eq!(palette, config);
+        assert!(Game::parse("Game 3: 1 violet", &config).is_ok());
+        assert!(Game::parse("Game 3: 1 red", &config).is_err());
+        assert!(Palette::from_str("red,").is_err());
+    }
+}
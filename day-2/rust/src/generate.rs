@@ -0,0 +1,334 @@
+use std::cmp::max;
+use std::fmt;
+use std::io::{self, Write};
+use std::str::FromStr;
+use crate::game::{Bag, Game, Overflow, Palette, Pull, Round};
+
+// SplitMix64, which is plenty for test data and keeps a seed's output the
+// same on every platform and version.
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Rng {
+        Rng(seed)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    // Uniform in 0..n, n must not be 0
+    pub fn below(&mut self, n: u64) -> u64 {
+        self.next_u64() % n
+    }
+
+    // Uniform in [0, 1)
+    pub fn unit(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+// How many cubes a single pull shows.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Counts {
+    Uniform { min: u64, max: u64 },
+    // Poisson with the given mean, but never less than 1
+    Poisson { mean: f64 }
+}
+
+impl Counts {
+    fn sample(&self, rng: &mut Rng) -> u64 {
+        match *self {
+            Counts::Uniform { min, max } => match (max - min).checked_add(1) {
+                Some(span) => min + rng.below(span),
+                None => rng.next_u64()
+            },
+            Counts::Poisson { mean } => {
+                let limit = (-mean).exp();
+                let mut k = 0u64;
+                let mut p = rng.unit();
+                while p > limit {
+                    k += 1;
+                    p *= rng.unit();
+                }
+                k.max(1)
+            }
+        }
+    }
+}
+
+// "uniform:MIN:MAX" or "poisson:MEAN"
+impl FromStr for Counts {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let error = || format!("Expected uniform:MIN:MAX or poisson:MEAN, found {:?}", s);
+        let parts: Vec<&str> = s.split(':').collect();
+        match parts.as_slice() {
+            ["uniform", min, max] => {
+                let min = u64::from_str(min).map_err(|_| error())?;
+                let max = u64::from_str(max).map_err(|_| error())?;
+                if min == 0 || min > max {
+                    return Err(format!("Expected 1 <= MIN <= MAX for uniform counts, found {}:{}", min, max));
+                }
+                Ok(Counts::Uniform { min, max })
+            },
+            // Past a mean of 700 the sampler's exp(-mean) rounds to zero
+            ["poisson", mean] => match f64::from_str(mean) {
+                Ok(mean) if mean > 0.0 && mean <= 700.0 => Ok(Counts::Poisson { mean }),
+                _ => Err(format!("The Poisson mean must be between 0 and 700, found {:?}", mean))
+            },
+            _ => Err(error())
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Spec {
+    pub seed: u64,
+    pub games: usize,
+    // Every game has between min and max rounds, both included
+    pub rounds: (usize, usize),
+    pub palette: Palette,
+    pub counts: Counts,
+    // The bag the possible games stay within and the impossible ones break
+    pub bag: Bag,
+    // Share of the games that break the bag, rounded to a whole game
+    pub impossible: f64
+}
+
+impl Default for Spec {
+    fn default() -> Self {
+        Spec {
+            seed: 0,
+            games: 100,
+            rounds: (1, 6),
+            palette: Palette::from_str("red, green, blue").expect("Expected a palette"),
+            counts: Counts::Uniform { min: 1, max: 20 },
+            bag: Bag::default().with("red", 12).with("green", 13).with("blue", 14),
+            impossible: 0.5
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Generated {
+    pub games: Vec<Game>,
+    // What the ID sum and power sum of `games` have to come to, worked out
+    // while generating rather than by the solvers they are meant to check
+    pub id_sum: u64,
+    pub power_sum: u64
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GenerateError {
+    NoRounds,
+    // Every colour in the palette has no cubes in the bag
+    EmptyBag,
+    // Every colour in the palette already has as many cubes as a u64 holds
+    Unbreakable,
+    Overflow(Overflow)
+}
+
+impl fmt::Display for GenerateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GenerateError::NoRounds => write!(f, "Every game needs at least one round"),
+            GenerateError::EmptyBag => write!(f, "The bag holds no cubes of any colour in the palette"),
+            GenerateError::Unbreakable => write!(f, "No pull can break a bag that is already as full as a u64 allows"),
+            GenerateError::Overflow(overflow) => write!(f, "{}", overflow)
+        }
+    }
+}
+
+pub fn generate(spec: &Spec) -> Result<Generated, GenerateError> {
+    let (min_rounds, max_rounds) = spec.rounds;
+    if max_rounds == 0 || min_rounds > max_rounds {
+        return Err(GenerateError::NoRounds);
+    }
+    // Only colours with room in the bag can show up in a possible game
+    let usable: Vec<&String> = spec.palette.colours().iter().filter(|c| spec.bag.count(c) > 0).collect();
+    if usable.is_empty() {
+        return Err(GenerateError::EmptyBag);
+    }
+    let breakable: Vec<&String> = spec.palette.colours().iter().filter(|c| spec.bag.count(c) < u64::MAX).collect();
+
+    let mut rng = Rng::new(spec.seed);
+    let impossible_count = (spec.impossible.clamp(0.0, 1.0) * spec.games as f64).round() as usize;
+    if impossible_count > 0 && breakable.is_empty() {
+        return Err(GenerateError::Unbreakable);
+    }
+    let mut impossible = vec!{false; spec.games};
+    for i in shuffled(spec.games, &mut rng).into_iter().take(impossible_count) {
+        impossible[i] = true;
+    }
+
+    let mut generated = Generated { games: Vec::with_capacity(spec.games), id_sum: 0, power_sum: 0 };
+    for (i, &breaks) in impossible.iter().enumerate() {
+        let id = (i + 1) as u64;
+        let round_count = min_rounds.max(1) + rng.below((max_rounds - min_rounds.max(1) + 1) as u64) as usize;
+        let mut rounds: Vec<Round> = (0..round_count).map(|_| possible_round(spec, &usable, &mut rng)).collect();
+        if breaks {
+            break_round(spec, &breakable, &mut rounds, &mut rng);
+        } else {
+            generated.id_sum = generated.id_sum.checked_add(id)
+                .ok_or(GenerateError::Overflow(Overflow { game: id, what: "the sum of ids up to it" }))?;
+        }
+
+        let mut smallest = Bag::default();
+        for pull in rounds.iter().flat_map(|round| round.pulls.iter()) {
+            let count = max(pull.count, smallest.count(&pull.colour));
+            smallest = smallest.with(&pull.colour, count);
+        }
+        let power = smallest.power(&spec.palette)
+            .ok_or(GenerateError::Overflow(Overflow { game: id, what: "the power of its minimal bag" }))?;
+        generated.power_sum = generated.power_sum.checked_add(power)
+            .ok_or(GenerateError::Overflow(Overflow { game: id, what: "the sum of powers up to it" }))?;
+        generated.games.push(Game { id, rounds });
+    }
+    Ok(generated)
+}
+
+// Writes the log, one game per line.
+pub fn write_log(out: &mut impl Write, generated: &Generated) -> io::Result<()> {
+    for game in &generated.games {
+        writeln!(out, "{}", game)?;
+    }
+    Ok(())
+}
+
+// Writes what solving the log should give, along with the bag and palette
+// to solve it with.
+pub fn write_expected(out: &mut impl Write, spec: &Spec, generated: &Generated) -> io::Result<()> {
+    writeln!(out, "seed {}", spec.seed)?;
+    writeln!(out, "bag {}", spec.bag)?;
+    writeln!(out, "palette {}", spec.palette)?;
+    writeln!(out, "ids {}", generated.id_sum)?;
+    writeln!(out, "powers {}", generated.power_sum)
+}
+
+fn shuffled(n: usize, rng: &mut Rng) -> Vec<usize> {
+    let mut indices: Vec<usize> = (0..n).collect();
+    for i in (1..n).rev() {
+        indices.swap(i, rng.below(i as u64 + 1) as usize);
+    }
+    indices
+}
+
+// Some of the usable colours in a random order, each within the bag.
+fn possible_round(spec: &Spec, usable: &[&String], rng: &mut Rng) -> Round {
+    let order = shuffled(usable.len(), rng);
+    let shown = 1 + rng.below(usable.len() as u64) as usize;
+    let pulls = order.into_iter().take(shown).map(|c| {
+        let colour = usable[c];
+        Pull { count: count_within(spec.counts, spec.bag.count(colour), rng), colour: colour.clone() }
+    }).collect();
+    Round { pulls }
+}
+
+fn count_within(counts: Counts, limit: u64, rng: &mut Rng) -> u64 {
+    for _ in 0..100 {
+        let count = counts.sample(rng);
+        if count <= limit {
+            return count;
+        }
+    }
+    // The distribution hardly ever fits under this limit
+    1 + rng.below(limit)
+}
+
+// Pushes one pull in one round over the bag's limit for its colour.
+fn break_round(spec: &Spec, breakable: &[&String], rounds: &mut [Round], rng: &mut Rng) {
+    let round = &mut rounds[rng.below(rounds.len() as u64) as usize];
+    let colour = breakable[rng.below(breakable.len() as u64) as usize];
+    let limit = spec.bag.count(colour);
+    let room = (u64::MAX - limit).min(5);
+    let count = limit + 1 + rng.below(room);
+    match round.pulls.iter_mut().find(|pull| &pull.colour == colour) {
+        Some(pull) => pull.count = count,
+        None => {
+            let at = rng.below(round.pulls.len() as u64 + 1) as usize;
+            round.pulls.insert(at, Pull { count, colour: colour.clone() });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spec(seed: u64) -> Spec {
+        Spec { seed, ..Spec::default() }
+    }
+
+    #[test]
+    fn test_same_seed_same_log() {
+        let a = generate(&spec(7)).expect("Expected a log");
+        assert_eq!(a, generate(&spec(7)).expect("Expected a log"));
+        assert_ne!(a.games, generate(&spec(8)).expect("Expected a log").games);
+    }
+
+    #[test]
+    fn test_matches_solvers() {
+        let spec = Spec {
+            games: 200,
+            rounds: (2, 4),
+            palette: Palette::from_str("red, green, blue, yellow").expect("Expected a palette"),
+            counts: Counts::Poisson { mean: 6.0 },
+            bag: Bag::from_str("8 red, 9 green, 10 blue, 3 yellow").expect("Expected a bag"),
+            impossible: 0.25,
+            seed: 2023
+        };
+        let generated = generate(&spec).expect("Expected a log");
+        let mut log: Vec<u8> = Vec::new();
+        write_log(&mut log, &generated).expect("Expected to write");
+
+        let games: Vec<Game> = String::from_utf8(log).expect("Expected utf8").lines()
+            .map(|line| Game::parse(line, &spec.palette).expect("Expected a game"))
+            .collect();
+        assert_eq!(generated.games, games);
+        let possible: Vec<&Game> = games.iter().filter(|game| game.is_possible(&spec.bag)).collect();
+        assert_eq!(150, possible.len());
+        assert_eq!(generated.id_sum, possible.iter().map(|game| game.id).sum::<u64>());
+        assert_eq!(generated.power_sum, games.iter().map(|game| game.power(&spec.palette).expect("Expected a power")).sum::<u64>());
+        for game in &games {
+            assert!((2..=4).contains(&game.rounds.len()));
+        }
+    }
+
+    #[test]
+    fn test_expected_file() {
+        let spec = Spec { games: 3, ..spec(1) };
+        let generated = generate(&spec).expect("Expected a log");
+        let mut out: Vec<u8> = Vec::new();
+        write_expected(&mut out, &spec, &generated).expect("Expected to write");
+        let expected = String::from_utf8(out).expect("Expected utf8");
+        assert!(expected.starts_with("seed 1\nbag 14 blue, 13 green, 12 red\npalette red, green, blue\n"));
+        assert!(expected.ends_with(&format!("ids {}\npowers {}\n", generated.id_sum, generated.power_sum)));
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(Err(GenerateError::NoRounds), generate(&Spec { rounds: (3, 2), ..Spec::default() }));
+        let bag = Bag::from_str("5 pink").expect("Expected a bag");
+        assert_eq!(Err(GenerateError::EmptyBag), generate(&Spec { bag, ..Spec::default() }));
+        let bag = Bag::default().with("red", u64::MAX).with("green", u64::MAX).with("blue", u64::MAX);
+        assert_eq!(Err(GenerateError::Unbreakable), generate(&Spec { bag, ..Spec::default() }));
+        let spec = Spec { counts: Counts::Uniform { min: 1 << 32, max: 1 << 32 }, bag: Bag::from_str("4294967296 red, 4294967296 green, 4294967296 blue").expect("Expected a bag"), ..Spec::default() };
+        assert!(matches!(generate(&Spec { impossible: 0.0, ..spec }), Err(GenerateError::Overflow(_))));
+    }
+
+    #[test]
+    fn test_parse_counts() {
+        assert_eq!(Ok(Counts::Uniform { min: 1, max: 20 }), Counts::from_str("uniform:1:20"));
+        assert_eq!(Ok(Counts::Poisson { mean: 4.5 }), Counts::from_str("poisson:4.5"));
+        assert!(Counts::from_str("uniform:0:20").is_err());
+        assert!(Counts::from_str("uniform:9:2").is_err());
+        assert!(Counts::from_str("poisson:800").is_err());
+        assert!(Counts::from_str("normal:3").is_err());
+    }
+}
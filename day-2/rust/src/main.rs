@@ -1,96 +1,194 @@
-use std::cmp::max;
+use std::env;
 use std::fs::File;
-use std::io::{BufRead, BufReader};
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+use std::process::exit;
 use std::str::FromStr;
 use std::sync::mpsc::{channel, Sender};
+use std::sync::Arc;
 use futures::executor::ThreadPool;
+use crate::estimate::Estimator;
+use crate::game::{Bag, Game, Overflow, Palette};
+use crate::generate::Spec;
+use crate::record::{Format, Record};
 
-fn main() {
-    let input_file = File::open("input.txt").expect("Could not open the file");
-    let reader = BufReader::new(input_file);
-    let (tx, rx) = channel();
+mod cli;
+mod estimate;
+mod game;
+mod generate;
+mod query;
+mod record;
 
-    let max_cubes = MaxCubes { red: 12, green: 13, blue: 14};
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let options = match cli::parse(&args) {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("{}", e);
+            exit(2);
+        }
+    };
 
-    let pool: ThreadPool = ThreadPool::new().expect("Couldn't create a threadpool");
+    if let Some(spec) = &options.generate {
+        write_generated(spec, &options.output);
+        return;
+    }
 
-    reader.lines().for_each( |line_result: Result<String, std::io::Error>| {
+    let reader: Box<dyn BufRead> = match &options.input {
+        Some(path) if path.as_os_str() == "-" => Box::new(BufReader::new(io::stdin())),
+        Some(path) => Box::new(BufReader::new(File::open(path).expect("Could not open the file"))),
+        None => Box::new(BufReader::new(File::open("input.txt").expect("Could not open the file")))
+    };
 
-        let txc = tx.clone();
-        let mcc = max_cubes.clone();
+    let mut games: Vec<Game> = Vec::new();
+    for (i, line_result) in reader.lines().enumerate() {
         let line = line_result.expect("Expected To read line");
+        let parsed = match &options.palette {
+            Some(palette) => Game::parse(&line, palette),
+            None => Game::from_str(&line)
+        };
+        match parsed {
+            Ok(game) => games.push(game),
+            Err(e) => {
+                eprintln!("Line {}, {}: {}", i + 1, e, line);
+                exit(1);
+            }
+        }
+    }
+    let palette = Arc::new(options.palette.unwrap_or_else(|| Palette::from_games(&games)));
+    if options.report {
+        print_report(&games, &options.bag);
+    }
+    for query in &options.queries {
+        println!("{}", or_exit(query::run(query, &games, &palette)));
+    }
+    if let Some(estimator) = &options.estimator {
+        print_estimates(&games, &palette, estimator);
+    }
+    let records = solve(games, Arc::new(options.bag), Arc::clone(&palette));
+    if let Some(path) = &options.records {
+        let mut out = BufWriter::new(File::create(path).expect("Could not create the records file"));
+        record::write(&mut out, Format::from_path(path), &records, &palette).expect("Unable to write the records");
+    }
+
+    if options.mode.ids() {
+        println!("Sum of possible games ids is : {}", or_exit(record::id_sum(&records)));
+    }
+    if options.mode.powers() {
+        println!("Sum of all games powers is : {}", or_exit(record::power_sum(&records)));
+    }
+}
+
+fn or_exit<T>(result: Result<T, Overflow>) -> T {
+    match result {
+        Ok(value) => value,
+        Err(e) => {
+            eprintln!("{}", e);
+            exit(1);
+        }
+    }
+}
+
+// Works out every game's record on the pool and hands them back in id order.
+fn solve(games: Vec<Game>, bag: Arc<Bag>, palette: Arc<Palette>) -> Vec<Record> {
+    let (tx, rx) = channel();
+    let pool: ThreadPool = ThreadPool::new().expect("Couldn't create a threadpool");
+    for game in games {
+        let tx: Sender<Record> = tx.clone();
+        let palette = Arc::clone(&palette);
+        let bag = Arc::clone(&bag);
         let future = async move {
-            sum_powers(line, txc)
+            tx.send(Record::new(&game, &bag, &palette)).expect("Unable to send message")
         };
         pool.spawn_ok(future);
-    });
+    }
     drop(tx);
+    record::in_id_order(rx.iter().collect())
+}
 
-    let sum: u32 = rx.iter().fold(0u32, |acc, x| acc+ u32::from(x));
-    println!("Sum of all games powers is : {}", sum);
+fn write_generated(spec: &Spec, output: &Path) {
+    let generated = match generate::generate(spec) {
+        Ok(generated) => generated,
+        Err(e) => {
+            eprintln!("{}", e);
+            exit(1);
+        }
+    };
+    let expected = output.with_extension("expected");
+    let mut log = BufWriter::new(File::create(output).expect("Could not create the log"));
+    generate::write_log(&mut log, &generated).expect("Unable to write the log");
+    log.flush().expect("Unable to write the log");
+    let mut out = File::create(&expected).expect("Could not create the expected sums");
+    generate::write_expected(&mut out, spec, &generated).expect("Unable to write the expected sums");
+    println!("Wrote {} games to {} and their sums to {}", generated.games.len(), output.display(), expected.display());
 }
 
-#[derive(Copy, Clone)]
-struct MaxCubes {
-    red: u8,
-    green: u8,
-    blue: u8
+fn print_report(games: &[Game], bag: &Bag) {
+    let mut impossible = 0;
+    for game in games {
+        let verdict = game.check(bag);
+        if verdict.is_possible() {
+            continue;
+        }
+        impossible += 1;
+        println!("Game {} is impossible:", game.id);
+        for violation in &verdict.violations {
+            println!("  {}", violation);
+        }
+    }
+    println!("{} of {} games are impossible", impossible, games.len());
 }
 
-fn sum_powers(line: String, tx: Sender<u16>) -> () {
-    let mut max_cubes = MaxCubes{red: 0, green: 0, blue: 0};
-    let line: Vec<&str> = line.trim().split(":").collect();
-
-    line[1].trim().split(";").for_each( |round: &str| {
-        round.trim().split(",").for_each( |pull: &str| {
-            let split_pull: Vec<&str> = pull.trim().split(" ").collect();
-            let count = u8::from_str(split_pull[0])
-                .expect("Error parsing number of cubes pulled");
-            match split_pull[1] {
-                "red" => max_cubes.red = max(count, max_cubes.red),
-                "green" => max_cubes.green = max(count, max_cubes.green),
-                "blue" => max_cubes.blue = max(count, max_cubes.blue),
-                _ => println!("Unrecognized color")
-            }
-        })
-    });
+fn print_estimates(games: &[Game], palette: &Palette, estimator: &Estimator) {
+    println!("Most likely bag and {}% credible interval per game:", estimator.level * 100.0);
+    for game in games {
+        match estimator.estimate(game, palette) {
+            Ok(estimates) => {
+                let colours: Vec<String> = estimates.iter()
+                    .map(|e| format!("{} {} [{}, {}]", e.colour, e.most_likely, e.lower, e.upper))
+                    .collect();
+                println!("Game {}: {}", game.id, colours.join(", "));
+            },
+            Err(e) => println!("Game {}: {}", game.id, e)
+        }
+    }
+}
 
-    let power: u16 = (max_cubes.red as u16) * (max_cubes.green as u16) * (max_cubes.blue as u16);
-    tx.send(power).expect("Unable to send message")
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-}
+    fn get_example_input() -> Vec<Game> {
+        [
+            "Game 1: 3 blue, 4 red; 1 red, 2 green, 6 blue; 2 green",
+            "Game 2: 1 blue, 2 green; 3 green, 4 blue, 1 red; 1 green, 1 blue",
+            "Game 3: 8 green, 6 blue, 20 red; 5 blue, 4 red, 13 green; 5 green, 1 red",
+            "Game 4: 1 green, 3 red, 6 blue; 3 green, 6 red; 3 green, 15 blue, 14 red",
+            "Game 5: 6 red, 1 blue, 3 green; 2 blue, 1 red, 2 green",
+        ].iter().map(|l| Game::from_str(l).expect("Expected a game")).collect()
+    }
 
-fn sum_ids(line: String, max_cubes: MaxCubes, tx: Sender<u8>) -> () {
-    let line: Vec<&str> = line.trim().split(":").collect();
-    let game_id: Vec<&str> = line[0].split(" ").collect();
-    let id: u8 = u8::from_str(game_id[1]).expect("Invalid id");
-    if valid_game(line[1].trim(), &max_cubes) {
-        tx.send(id).expect("Channel Unavailable");
+    fn example_records() -> Vec<Record> {
+        let games = get_example_input();
+        let palette = Palette::from_games(&games);
+        let bag = Bag::from_str("12 red, 13 green, 14 blue").expect("Expected a bag");
+        solve(games, Arc::new(bag), Arc::new(palette))
     }
-}
 
-fn valid_game(game: &str, max_cubes: &MaxCubes) -> bool {
-    let mut valid = true;
-    game.split(";").for_each(|round: &str| {
-       if !valid_round(round, max_cubes) {
-           valid = false;
-       }
-    });
-    valid
-}
+    #[test]
+    fn test_example_part_one() {
+        assert_eq!(Ok(8), record::id_sum(&example_records()));
+    }
 
-fn valid_round(round: &str, max_cubes: &MaxCubes) -> bool {
-    let mut valid = true;
-    round.split(",").for_each(|cube_pull: &str| {
-        let pull: Vec<&str> = cube_pull.trim().split(" ").collect();
-        let count = u8::from_str(pull[0]).expect("Invalid cube count");
-        match pull[1] {
-            "red" => if count > max_cubes.red {valid = false;}
-            "green" => if count > max_cubes.green {valid = false;}
-            "blue" => if count > max_cubes.blue {valid = false;}
-            _ => valid = false
-        }
-    });
+    #[test]
+    fn test_example_part_two() {
+        assert_eq!(Ok(2286), record::power_sum(&example_records()));
+    }
 
-    valid
+    #[test]
+    fn test_records_in_id_order() {
+        let records = example_records();
+        assert_eq!(vec!{1, 2, 3, 4, 5}, records.iter().map(|r| r.id).collect::<Vec<u64>>());
+        assert_eq!(vec!{true, true, false, false, true}, records.iter().map(|r| r.possible).collect::<Vec<bool>>());
+    }
 }
@@ -0,0 +1,453 @@
+use std::fmt;
+use std::str::FromStr;
+use crate::game::{Bag, Game, Overflow, Palette, ParseError, Round};
+
+// Query      = Or
+// Or         = And ("or" And)*
+// And        = Not ("and" Not)*
+// Not        = "not" Not | "(" Query ")" | ("some" | "every") "(" Query ")" | Comparison
+// Comparison = Value ("<" | "<=" | ">" | ">=" | "=" | "!=") Value
+// Value      = number | "id" | "power" | "rounds" | "cubes" | colour
+//
+// Outside of `some` and `every` a colour is the most cubes of it any round
+// showed, so the game's minimal bag, and `cubes` is that bag's size. Inside
+// them both count the cubes of the round being looked at.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Query {
+    And(Box<Query>, Box<Query>),
+    Or(Box<Query>, Box<Query>),
+    Not(Box<Query>),
+    // At least one round matches
+    Some(Box<Query>),
+    // Every round matches
+    Every(Box<Query>),
+    Compare(Value, Op, Value)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Value {
+    Number(u64),
+    Id,
+    Power,
+    Rounds,
+    Cubes,
+    Colour(String)
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Op {
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Eq,
+    Ne
+}
+
+impl Op {
+    fn apply(&self, left: u64, right: u64) -> bool {
+        match self {
+            Op::Lt => left < right,
+            Op::Le => left <= right,
+            Op::Gt => left > right,
+            Op::Ge => left >= right,
+            Op::Eq => left == right,
+            Op::Ne => left != right
+        }
+    }
+}
+
+// What a query is looked at against: a game, and a round of it inside `some` and `every`.
+struct Scope<'a> {
+    game: &'a Game,
+    bag: &'a Bag,
+    palette: &'a Palette,
+    round: Option<&'a Round>
+}
+
+impl Query {
+    // `bag` is the game's minimal bag, worked out once by the caller.
+    pub fn matches(&self, game: &Game, bag: &Bag, palette: &Palette) -> Result<bool, Overflow> {
+        self.eval(&Scope { game, bag, palette, round: None })
+    }
+
+    fn eval(&self, scope: &Scope) -> Result<bool, Overflow> {
+        Ok(match self {
+            Query::And(left, right) => left.eval(scope)? && right.eval(scope)?,
+            Query::Or(left, right) => left.eval(scope)? || right.eval(scope)?,
+            Query::Not(query) => !query.eval(scope)?,
+            Query::Some(query) => {
+                for round in &scope.game.rounds {
+                    if query.eval(&Scope { round: Some(round), ..*scope })? {
+                        return Ok(true);
+                    }
+                }
+                false
+            },
+            Query::Every(query) => {
+                for round in &scope.game.rounds {
+                    if !query.eval(&Scope { round: Some(round), ..*scope })? {
+                        return Ok(false);
+                    }
+                }
+                true
+            },
+            Query::Compare(left, op, right) => op.apply(left.eval(scope)?, right.eval(scope)?)
+        })
+    }
+}
+
+impl Value {
+    fn eval(&self, scope: &Scope) -> Result<u64, Overflow> {
+        let overflow = |what| Overflow { game: scope.game.id, what };
+        match (self, scope.round) {
+            (Value::Number(n), _) => Ok(*n),
+            (Value::Id, _) => Ok(scope.game.id),
+            (Value::Power, _) => scope.bag.power(scope.palette).ok_or(overflow("the power of its minimal bag")),
+            (Value::Rounds, _) => Ok(scope.game.rounds.len() as u64),
+            (Value::Cubes, Some(round)) => round.pulls.iter().try_fold(0u64, |sum, pull| sum.checked_add(pull.count))
+                .ok_or(overflow("the cubes in a round")),
+            (Value::Cubes, None) => scope.bag.size(scope.palette).ok_or(overflow("the cubes in its minimal bag")),
+            (Value::Colour(colour), Some(round)) => round.pulls.iter().filter(|pull| &pull.colour == colour)
+                .try_fold(0u64, |sum, pull| sum.checked_add(pull.count))
+                .ok_or(overflow("the cubes of one colour in a round")),
+            (Value::Colour(colour), None) => Ok(scope.bag.count(colour))
+        }
+    }
+}
+
+impl FromStr for Query {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parser = Parser { tokens: tokenize(s)?, pos: 0 };
+        let query = parser.or()?;
+        match parser.peek() {
+            Token::End => Ok(query),
+            _ => Err(parser.error("'and', 'or' or the end of the query"))
+        }
+    }
+}
+
+// The games a query picked out and what they add up to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Summary {
+    pub ids: Vec<u64>,
+    pub id_sum: u64,
+    pub power_sum: u64
+}
+
+impl Summary {
+    pub fn count(&self) -> usize {
+        self.ids.len()
+    }
+}
+
+impl fmt::Display for Summary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let ids: Vec<String> = self.ids.iter().map(u64::to_string).collect();
+        writeln!(f, "Matching games: {}", if ids.is_empty() { String::from("none") } else { ids.join(", ") })?;
+        write!(f, "Count {}, sum of ids {}, sum of powers {}", self.count(), self.id_sum, self.power_sum)
+    }
+}
+
+pub fn run(query: &Query, games: &[Game], palette: &Palette) -> Result<Summary, Overflow> {
+    let mut summary = Summary { ids: Vec::new(), id_sum: 0, power_sum: 0 };
+    for game in games {
+        let bag = game.minimal_bag();
+        if !query.matches(game, &bag, palette)? {
+            continue;
+        }
+        let power = bag.power(palette).ok_or(Overflow { game: game.id, what: "the power of its minimal bag" })?;
+        summary.ids.push(game.id);
+        summary.id_sum = summary.id_sum.checked_add(game.id)
+            .ok_or(Overflow { game: game.id, what: "the sum of matching ids up to it" })?;
+        summary.power_sum = summary.power_sum.checked_add(power)
+            .ok_or(Overflow { game: game.id, what: "the sum of matching powers up to it" })?;
+    }
+    Ok(summary)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Number(String),
+    Word(String),
+    Symbol(&'static str),
+    End
+}
+
+impl Token {
+    fn describe(&self) -> String {
+        match self {
+            Token::Number(s) | Token::Word(s) => format!("{:?}", s),
+            Token::Symbol(s) => format!("{:?}", s),
+            Token::End => String::from("end of query")
+        }
+    }
+}
+
+// Longest first, so "<=" is not read as "<" then "="
+const SYMBOLS: [&str; 9] = ["<=", ">=", "!=", "==", "<", ">", "=", "(", ")"];
+
+// Every token along with the column it starts at, counting characters from 1.
+fn tokenize(s: &str) -> Result<Vec<(usize, Token)>, ParseError> {
+    let chars: Vec<char> = s.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        let start = i;
+        if c.is_whitespace() {
+            i += 1;
+            continue;
+        }
+        let token = if c.is_ascii_digit() {
+            while i < chars.len() && chars[i].is_ascii_digit() {
+                i += 1;
+            }
+            Token::Number(chars[start..i].iter().collect())
+        } else if c.is_alphabetic() {
+            while i < chars.len() && chars[i].is_alphabetic() {
+                i += 1;
+            }
+            Token::Word(chars[start..i].iter().collect())
+        } else {
+            let rest: String = chars[i..].iter().take(2).collect();
+            match SYMBOLS.iter().find(|symbol| rest.starts_with(**symbol)) {
+                Some(symbol) => {
+                    i += symbol.len();
+                    Token::Symbol(symbol)
+                },
+                None => return Err(ParseError { column: i + 1, expected: "a number, a word or an operator", found: format!("{:?}", c.to_string()) })
+            }
+        };
+        tokens.push((start + 1, token));
+    }
+    tokens.push((chars.len() + 1, Token::End));
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<(usize, Token)>,
+    pos: usize
+}
+
+impl Parser {
+    fn or(&mut self) -> Result<Query, ParseError> {
+        let mut query = self.and()?;
+        while self.eat_word("or") {
+            query = Query::Or(Box::new(query), Box::new(self.and()?));
+        }
+        Ok(query)
+    }
+
+    fn and(&mut self) -> Result<Query, ParseError> {
+        let mut query = self.not()?;
+        while self.eat_word("and") {
+            query = Query::And(Box::new(query), Box::new(self.not()?));
+        }
+        Ok(query)
+    }
+
+    fn not(&mut self) -> Result<Query, ParseError> {
+        if self.eat_word("not") {
+            return Ok(Query::Not(Box::new(self.not()?)));
+        }
+        if self.eat_symbol("(") {
+            return self.close();
+        }
+        if self.eat_word("some") {
+            self.expect_symbol("(", "'(' after some")?;
+            return Ok(Query::Some(Box::new(self.close()?)));
+        }
+        if self.eat_word("every") {
+            self.expect_symbol("(", "'(' after every")?;
+            return Ok(Query::Every(Box::new(self.close()?)));
+        }
+        let left = self.value()?;
+        let op = self.op()?;
+        let right = self.value()?;
+        Ok(Query::Compare(left, op, right))
+    }
+
+    // The rest of a bracketed query, up to and including the ')'.
+    fn close(&mut self) -> Result<Query, ParseError> {
+        let query = self.or()?;
+        self.expect_symbol(")", "')'")?;
+        Ok(query)
+    }
+
+    fn value(&mut self) -> Result<Value, ParseError> {
+        let value = match self.peek() {
+            Token::Number(digits) => Value::Number(u64::from_str(digits).map_err(|_| ParseError {
+                column: self.column(),
+                expected: "a number that fits in 64 bits",
+                found: format!("{:?}", digits)
+            })?),
+            Token::Word(word) => match word.as_str() {
+                "and" | "or" | "not" | "some" | "every" => return Err(self.error("a number, a field or a colour")),
+                "id" => Value::Id,
+                "power" => Value::Power,
+                "rounds" => Value::Rounds,
+                "cubes" => Value::Cubes,
+                colour => Value::Colour(String::from(colour))
+            },
+            _ => return Err(self.error("a number, a field or a colour"))
+        };
+        self.pos += 1;
+        Ok(value)
+    }
+
+    fn op(&mut self) -> Result<Op, ParseError> {
+        let op = match self.peek() {
+            Token::Symbol("<") => Op::Lt,
+            Token::Symbol("<=") => Op::Le,
+            Token::Symbol(">") => Op::Gt,
+            Token::Symbol(">=") => Op::Ge,
+            Token::Symbol("=") | Token::Symbol("==") => Op::Eq,
+            Token::Symbol("!=") => Op::Ne,
+            _ => return Err(self.error("a comparison (<, <=, >, >=, = or !=)"))
+        };
+        self.pos += 1;
+        Ok(op)
+    }
+
+    fn peek(&self) -> &Token {
+        &self.tokens[self.pos].1
+    }
+
+    fn column(&self) -> usize {
+        self.tokens[self.pos].0
+    }
+
+    fn eat_word(&mut self, word: &str) -> bool {
+        if matches!(self.peek(), Token::Word(w) if w == word) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn eat_symbol(&mut self, symbol: &str) -> bool {
+        if matches!(self.peek(), Token::Symbol(s) if *s == symbol) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect_symbol(&mut self, symbol: &str, expected: &'static str) -> Result<(), ParseError> {
+        if self.eat_symbol(symbol) { Ok(()) } else { Err(self.error(expected)) }
+    }
+
+    fn error(&self, expected: &'static str) -> ParseError {
+        ParseError { column: self.column(), expected, found: self.peek().describe() }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn games() -> Vec<Game> {
+        [
+            "Game 1: 3 blue, 4 red; 1 red, 2 green, 6 blue; 2 green",
+            "Game 2: 1 blue, 2 green; 3 green, 4 blue, 1 red; 1 green, 1 blue",
+            "Game 3: 8 green, 6 blue, 20 red; 5 blue, 4 red, 13 green; 5 green, 1 red",
+            "Game 4: 1 green, 3 red, 6 blue; 3 green, 6 red; 3 green, 15 blue, 14 red",
+            "Game 5: 6 red, 1 blue, 3 green; 2 blue, 1 red, 2 green",
+        ].iter().map(|l| Game::from_str(l).expect("Expected a game")).collect()
+    }
+
+    fn ids(query: &str) -> Vec<u64> {
+        let games = games();
+        let query = Query::from_str(query).expect("Expected a query");
+        run(&query, &games, &Palette::from_games(&games)).expect("Expected a summary").ids
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(
+            Ok(Query::Some(Box::new(Query::And(
+                Box::new(Query::Compare(Value::Colour(String::from("red")), Op::Gt, Value::Number(10))),
+                Box::new(Query::Compare(Value::Colour(String::from("blue")), Op::Lt, Value::Number(3)))
+            )))),
+            Query::from_str("some(red>10 and blue<3)")
+        );
+        // and binds tighter than or
+        assert_eq!(
+            Query::from_str("(id = 1 or id = 2) or id = 3 and power >= 10"),
+            Query::from_str("(id = 1 or id = 2) or (id = 3 and power >= 10)")
+        );
+    }
+
+    #[test]
+    fn test_round_queries() {
+        assert_eq!(vec!{3, 4}, ids("some(red > 10)"));
+        assert_eq!(vec!{3}, ids("some(red > 10 and blue < 10)"));
+        assert_eq!(vec!{1, 2, 5}, ids("every(red <= 12 and green <= 13 and blue <= 14)"));
+        assert_eq!(vec!{3, 4}, ids("some(cubes >= 20)"));
+    }
+
+    #[test]
+    fn test_game_queries() {
+        assert_eq!(vec!{3, 4}, ids("power > 500"));
+        assert_eq!(vec!{1, 2, 5}, ids("not (red > 12 or green > 13 or blue > 14)"));
+        assert_eq!(vec!{2, 3, 4}, ids("rounds = 3 and id != 1"));
+        assert_eq!(vec!{1}, ids("cubes = 12"));
+        assert_eq!(Vec::<u64>::new(), ids("pink > 0"));
+    }
+
+    #[test]
+    fn test_summary() {
+        let games = games();
+        let query = Query::from_str("power < 100").expect("Expected a query");
+        let summary = run(&query, &games, &Palette::from_games(&games)).expect("Expected a summary");
+        assert_eq!(Summary { ids: vec!{1, 2, 5}, id_sum: 8, power_sum: 48 + 12 + 36 }, summary);
+        assert_eq!("Matching games: 1, 2, 5\nCount 3, sum of ids 8, sum of powers 96", summary.to_string());
+    }
+
+    #[test]
+    fn test_overflow_names_the_game() {
+        let games: Vec<Game> = [
+            "Game 1: 2 red, 2 blue",
+            "Game 2: 18446744073709551615 red, 2 blue",
+        ].iter().map(|l| Game::from_str(l).expect("Expected a game")).collect();
+        let palette = Palette::from_games(&games);
+        let power = Query::from_str("power > 1").expect("Expected a query");
+        assert_eq!(Err(Overflow { game: 2, what: "the power of its minimal bag" }), run(&power, &games, &palette));
+        let cubes = Query::from_str("some(cubes > 1)").expect("Expected a query");
+        assert_eq!(Err(Overflow { game: 2, what: "the cubes in a round" }), run(&cubes, &games, &palette));
+        // Only games that match are added up
+        let red = Query::from_str("red < 5").expect("Expected a query");
+        assert_eq!(Ok(vec!{1}), run(&red, &games, &palette).map(|summary| summary.ids));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(
+            Err(ParseError { column: 5, expected: "a comparison (<, <=, >, >=, = or !=)", found: String::from("\"10\"") }),
+            Query::from_str("red 10")
+        );
+        assert_eq!(
+            Err(ParseError { column: 15, expected: "')'", found: String::from("end of query") }),
+            Query::from_str("some(red > 10 ")
+        );
+        assert_eq!(
+            Err(ParseError { column: 6, expected: "'(' after some", found: String::from("\"red\"") }),
+            Query::from_str("some red > 1")
+        );
+        assert_eq!(
+            Err(ParseError { column: 9, expected: "'and', 'or' or the end of the query", found: String::from("\"blue\"") }),
+            Query::from_str("red > 1 blue < 2")
+        );
+        assert_eq!(
+            Err(ParseError { column: 5, expected: "a number, a word or an operator", found: String::from("\"&\"") }),
+            Query::from_str("red & blue")
+        );
+        assert!(Query::from_str("red > and").is_err());
+        assert!(Query::from_str("id > 99999999999999999999").is_err());
+    }
+}
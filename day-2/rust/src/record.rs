@@ -0,0 +1,194 @@
+use std::fmt::Write as _;
+use std::io::{self, Write};
+use std::path::Path;
+use crate::game::{Bag, Game, Overflow, Palette};
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Format {
+    Csv,
+    Json
+}
+
+impl Format {
+    // JSON for `.json` files, CSV for anything else
+    pub fn from_path(path: &Path) -> Format {
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("json") => Format::Json,
+            _ => Format::Csv
+        }
+    }
+}
+
+// Everything the solvers work out about one game.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Record {
+    pub id: u64,
+    pub minimal_bag: Bag,
+    pub power: Result<u64, Overflow>,
+    // Whether the game fits in the bag given on the command line
+    pub possible: bool
+}
+
+impl Record {
+    pub fn new(game: &Game, bag: &Bag, palette: &Palette) -> Record {
+        Record { id: game.id, minimal_bag: game.minimal_bag(), power: game.power(palette), possible: game.is_possible(bag) }
+    }
+}
+
+// Sorts records by game id, which is the order every total and export uses.
+pub fn in_id_order(mut records: Vec<Record>) -> Vec<Record> {
+    records.sort_by_key(|record| record.id);
+    records
+}
+
+pub fn id_sum(records: &[Record]) -> Result<u64, Overflow> {
+    records.iter().filter(|record| record.possible).try_fold(0u64, |sum, record| {
+        sum.checked_add(record.id).ok_or(Overflow { game: record.id, what: "the sum of ids up to it" })
+    })
+}
+
+pub fn power_sum(records: &[Record]) -> Result<u64, Overflow> {
+    records.iter().try_fold(0u64, |sum, record| {
+        sum.checked_add(record.power.clone()?).ok_or(Overflow { game: record.id, what: "the sum of powers up to it" })
+    })
+}
+
+// One row or object per record, with a count for every colour in the palette.
+pub fn write(out: &mut impl Write, format: Format, records: &[Record], palette: &Palette) -> io::Result<()> {
+    match format {
+        Format::Csv => {
+            let colours: Vec<String> = palette.colours().iter().map(|c| csv_field(c)).collect();
+            writeln!(out, "id,{},power,possible,error", colours.join(","))?;
+            for record in records {
+                writeln!(out, "{}", csv_row(record, palette))?;
+            }
+        },
+        Format::Json => {
+            write!(out, "[")?;
+            for (i, record) in records.iter().enumerate() {
+                let separator = if i == 0 { "" } else { "," };
+                write!(out, "{}\n  {}", separator, json_object(record, palette))?;
+            }
+            writeln!(out, "\n]")?;
+        }
+    }
+    out.flush()
+}
+
+fn csv_row(record: &Record, palette: &Palette) -> String {
+    let mut row = record.id.to_string();
+    for colour in palette.colours() {
+        write!(row, ",{}", record.minimal_bag.count(colour)).expect("Writing to a String cannot fail");
+    }
+    match &record.power {
+        Ok(power) => write!(row, ",{},{},", power, record.possible),
+        Err(e) => write!(row, ",,{},{}", record.possible, csv_field(&e.to_string()))
+    }.expect("Writing to a String cannot fail");
+    row
+}
+
+fn csv_field(s: &str) -> String {
+    if s.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        String::from(s)
+    }
+}
+
+fn json_object(record: &Record, palette: &Palette) -> String {
+    let bag: Vec<String> = palette.colours().iter()
+        .map(|colour| format!("{}: {}", json_string(colour), record.minimal_bag.count(colour)))
+        .collect();
+    let power = match &record.power {
+        Ok(power) => format!("\"power\": {}, \"error\": null", power),
+        Err(e) => format!("\"power\": null, \"error\": {}", json_string(&e.to_string()))
+    };
+    format!(
+        "{{\"id\": {}, \"minimal_bag\": {{{}}}, \"possible\": {}, {}}}",
+        record.id, bag.join(", "), record.possible, power
+    )
+}
+
+fn json_string(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len() + 2);
+    escaped.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c if (c as u32) < 0x20 => write!(escaped, "\\u{:04x}", c as u32).expect("Writing to a String cannot fail"),
+            c => escaped.push(c)
+        }
+    }
+    escaped.push('"');
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn records() -> (Vec<Record>, Palette) {
+        let games: Vec<Game> = [
+            "Game 3: 8 green, 6 blue, 20 red; 5 blue, 4 red, 13 green",
+            "Game 1: 3 blue, 4 red; 1 red, 2 green, 6 blue; 2 green",
+            "Game 2: 18446744073709551615 red, 2 green, 1 blue",
+        ].iter().map(|l| Game::from_str(l).expect("Expected a game")).collect();
+        let palette = Palette::from_str("red, green, blue").expect("Expected a palette");
+        let bag = Bag::from_str("12 red, 13 green, 14 blue").expect("Expected a bag");
+        (in_id_order(games.iter().map(|game| Record::new(game, &bag, &palette)).collect()), palette)
+    }
+
+    fn export(format: Format) -> String {
+        let (records, palette) = records();
+        let mut out: Vec<u8> = Vec::new();
+        write(&mut out, format, &records, &palette).expect("Expected to write");
+        String::from_utf8(out).expect("Expected utf8")
+    }
+
+    #[test]
+    fn test_records() {
+        let (records, _) = records();
+        assert_eq!(vec!{1, 2, 3}, records.iter().map(|r| r.id).collect::<Vec<u64>>());
+        assert_eq!(Bag::from_str("4 red, 2 green, 6 blue"), Ok(records[0].minimal_bag.clone()));
+        assert_eq!(Ok(48), records[0].power);
+        assert!(records[0].possible);
+        assert!(!records[2].possible);
+        assert_eq!(Ok(1), id_sum(&records));
+        assert_eq!(Err(Overflow { game: 2, what: "the power of its minimal bag" }), power_sum(&records));
+        assert_eq!(Ok(48 + 1560), power_sum(&[records[0].clone(), records[2].clone()]));
+    }
+
+    #[test]
+    fn test_csv() {
+        let csv = export(Format::Csv);
+        let rows: Vec<&str> = csv.lines().collect();
+        assert_eq!(4, rows.len());
+        assert_eq!("id,red,green,blue,power,possible,error", rows[0]);
+        assert_eq!("1,4,2,6,48,true,", rows[1]);
+        assert_eq!("2,18446744073709551615,2,1,,false,Game 2: the power of its minimal bag does not fit in 64 bits", rows[2]);
+        assert_eq!("3,20,13,6,1560,false,", rows[3]);
+    }
+
+    #[test]
+    fn test_json() {
+        let json = export(Format::Json);
+        assert!(json.starts_with("[\n  {\"id\": 1, \"minimal_bag\": {\"red\": 4, \"green\": 2, \"blue\": 6}, \"possible\": true, \"power\": 48, \"error\": null},\n"));
+        assert!(json.contains("\"power\": null, \"error\": \"Game 2: the power of its minimal bag does not fit in 64 bits\""));
+        assert!(json.ends_with("\"power\": 1560, \"error\": null}\n]\n"));
+        let mut out: Vec<u8> = Vec::new();
+        write(&mut out, Format::Json, &[], &records().1).expect("Expected to write");
+        assert_eq!("[\n]\n", String::from_utf8(out).expect("Expected utf8"));
+    }
+
+    #[test]
+    fn test_format_from_path() {
+        assert_eq!(Format::Json, Format::from_path(Path::new("games.json")));
+        assert_eq!(Format::Csv, Format::from_path(Path::new("games.csv")));
+        assert_eq!(Format::Csv, Format::from_path(Path::new("games")));
+    }
+}
@@ -0,0 +1,130 @@
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use crate::rule::{self, Rule};
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Mode {
+    Parts,
+    Gears,
+    Both
+}
+
+impl FromStr for Mode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "1" | "part-one" | "parts" => Ok(Mode::Parts),
+            "2" | "part-two" | "gears" => Ok(Mode::Gears),
+            "both" => Ok(Mode::Both),
+            _ => Err(format!("Unknown mode {:?}, expected parts, gears or both", s))
+        }
+    }
+}
+
+impl Mode {
+    pub fn parts(&self) -> bool {
+        *self != Mode::Gears
+    }
+
+    pub fn gears(&self) -> bool {
+        *self != Mode::Parts
+    }
+}
+
+pub struct Options {
+    pub mode: Mode,
+    // `-` reads from stdin, `None` falls back to input.txt
+    pub input: Option<PathBuf>,
+    // Reported one by one instead of the puzzle's gear ratio when given
+    pub rules: Vec<Rule>,
+    // Prints the schematic coloured by what each cell counted as
+    pub render: bool,
+    // Writes the same highlights to an SVG here
+    pub svg: Option<PathBuf>,
+    // Writes the number and symbol graph here, as JSON for `.json` files and DOT otherwise
+    pub graph: Option<PathBuf>,
+    // Prints the graph's components, busiest symbol and shared numbers
+    pub graph_report: bool
+}
+
+impl Options {
+    // Everything but the sums needs the whole schematic in memory.
+    pub fn whole_grid(&self) -> bool {
+        self.render || self.svg.is_some() || self.graph.is_some() || self.graph_report
+    }
+}
+
+// Usage: day-3 [--mode parts|gears|both] [--input FILE|-] [--rule RULE]... [--rules FILE]
+//              [--render] [--svg FILE] [--graph FILE.dot|FILE.json] [--graph-report]
+pub fn parse(args: &[String]) -> Result<Options, String> {
+    let mut mode = Mode::Gears;
+    let mut input: Option<PathBuf> = None;
+    let mut rules: Vec<Rule> = Vec::new();
+    let mut render = false;
+    let mut svg: Option<PathBuf> = None;
+    let mut graph: Option<PathBuf> = None;
+    let mut graph_report = false;
+
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--mode" => mode = Mode::from_str(value_of(arg, iter.next())?)?,
+            "--input" => input = Some(PathBuf::from(value_of(arg, iter.next())?)),
+            "--rule" => rules.push(Rule::from_str(value_of(arg, iter.next())?)?),
+            "--rules" => rules.extend(rule::load(Path::new(value_of(arg, iter.next())?))?),
+            "--render" => render = true,
+            "--svg" => svg = Some(PathBuf::from(value_of(arg, iter.next())?)),
+            "--graph" => graph = Some(PathBuf::from(value_of(arg, iter.next())?)),
+            "--graph-report" => graph_report = true,
+            _ => return Err(format!("Unknown argument {:?}", arg))
+        }
+    }
+    Ok(Options { mode, input, rules, render, svg, graph, graph_report })
+}
+
+fn value_of<'a>(flag: &str, value: Option<&'a String>) -> Result<&'a str, String> {
+    value.map(|v| v.as_str()).ok_or(format!("Expected a value after {}", flag))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(s: &str) -> Vec<String> {
+        s.split_whitespace().map(String::from).collect()
+    }
+
+    #[test]
+    fn test_parse_options() {
+        let options = parse(&[]).expect("Expected options");
+        assert_eq!(Mode::Gears, options.mode);
+        assert_eq!(None, options.input);
+        assert_eq!(Mode::Parts, parse(&args("--mode 1")).expect("Expected options").mode);
+        assert_eq!(Mode::Both, parse(&args("--mode both")).expect("Expected options").mode);
+        assert_eq!(Some(PathBuf::from("-")), parse(&args("--input -")).expect("Expected options").input);
+        assert!(Mode::Both.parts() && Mode::Both.gears());
+        assert!(!Mode::Parts.gears() && !Mode::Gears.parts());
+        assert!(parse(&[]).expect("Expected options").rules.is_empty());
+        assert!(!parse(&[]).expect("Expected options").render);
+        assert!(parse(&args("--render")).expect("Expected options").render);
+        assert_eq!(Some(PathBuf::from("schematic.svg")), parse(&args("--svg schematic.svg")).expect("Expected options").svg);
+        let options = parse(&args("--graph schematic.dot --graph-report")).expect("Expected options");
+        assert_eq!(Some(PathBuf::from("schematic.dot")), options.graph);
+        assert!(options.graph_report && options.whole_grid());
+        assert!(!parse(&[]).expect("Expected options").whole_grid());
+        let options = parse(&[String::from("--rule"), String::from("hub # 3+ sum"), String::from("--rule"), String::from("gear * 2 product")])
+            .expect("Expected options");
+        assert_eq!(vec!{String::from("hub"), String::from("gear")}, options.rules.iter().map(|r| r.name.clone()).collect::<Vec<String>>());
+    }
+
+    #[test]
+    fn test_bad_options() {
+        assert!(parse(&args("--mode")).is_err());
+        assert!(parse(&args("--mode three")).is_err());
+        assert!(parse(&args("--gears")).is_err());
+        assert!(parse(&args("--rule")).is_err());
+        assert!(parse(&args("--svg")).is_err());
+        assert!(parse(&args("--rules missing-rules.txt")).is_err());
+    }
+}
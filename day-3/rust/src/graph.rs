@@ -0,0 +1,251 @@
+use std::collections::HashMap;
+use std::io::{self, Write};
+use std::path::Path;
+use crate::grid::{Grid, Point};
+use crate::{is_symbol, numbers, visit_eight_neighbors, EnginePart, PartsOverflow};
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Format {
+    Dot,
+    Json
+}
+
+impl Format {
+    // JSON for `.json` files, DOT for anything else
+    pub fn from_path(path: &Path) -> Format {
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("json") => Format::Json,
+            _ => Format::Dot
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Symbol {
+    pub at: Point,
+    pub symbol: u8
+}
+
+// Numbers on one side, symbols on the other, and an edge wherever a symbol
+// touches a number. Both kinds of node are kept in reading order, and edges
+// are (number, symbol) index pairs sorted the same way.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Graph {
+    pub numbers: Vec<EnginePart>,
+    pub symbols: Vec<Symbol>,
+    pub edges: Vec<(usize, usize)>,
+    // The same edges listed from each end, so neither side needs a scan
+    symbols_by_number: Vec<Vec<usize>>,
+    numbers_by_symbol: Vec<Vec<usize>>
+}
+
+// Indices into `Graph::numbers` and `Graph::symbols`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Component {
+    pub numbers: Vec<usize>,
+    pub symbols: Vec<usize>
+}
+
+impl Component {
+    pub fn len(&self) -> usize {
+        self.numbers.len() + self.symbols.len()
+    }
+}
+
+impl Graph {
+    pub fn build(grid: &Grid) -> Result<Graph, PartsOverflow> {
+        let numbers: Vec<EnginePart> = (0..grid.height()).flat_map(|row| numbers(grid, row)).collect::<Result<_, _>>()?;
+        let index: HashMap<EnginePart, usize> = numbers.iter().enumerate().map(|(i, &part)| (part, i)).collect();
+        let mut symbols: Vec<Symbol> = Vec::new();
+        let mut edges: Vec<(usize, usize)> = Vec::new();
+        for p in grid.points() {
+            let Some(symbol) = grid.get(p).filter(|&c| is_symbol(c)) else {
+                continue;
+            };
+            edges.extend(visit_eight_neighbors(grid, p)?.iter().map(|part| (index[part], symbols.len())));
+            symbols.push(Symbol { at: p, symbol });
+        }
+        edges.sort();
+        let mut symbols_by_number: Vec<Vec<usize>> = vec!{Vec::new(); numbers.len()};
+        let mut numbers_by_symbol: Vec<Vec<usize>> = vec!{Vec::new(); symbols.len()};
+        for &(n, s) in &edges {
+            symbols_by_number[n].push(s);
+            numbers_by_symbol[s].push(n);
+        }
+        Ok(Graph { numbers, symbols, edges, symbols_by_number, numbers_by_symbol })
+    }
+
+    pub fn symbols_of(&self, number: usize) -> impl Iterator<Item = usize> + '_ {
+        self.symbols_by_number[number].iter().copied()
+    }
+
+    pub fn numbers_of(&self, symbol: usize) -> impl Iterator<Item = usize> + '_ {
+        self.numbers_by_symbol[symbol].iter().copied()
+    }
+
+    // Every connected component, lone numbers and symbols included, ordered by
+    // their first number or else their first symbol.
+    pub fn components(&self) -> Vec<Component> {
+        // Numbers come first, then symbols, in one union-find
+        let mut parent: Vec<usize> = (0..self.numbers.len() + self.symbols.len()).collect();
+        fn root(parent: &mut [usize], mut node: usize) -> usize {
+            while parent[node] != node {
+                parent[node] = parent[parent[node]];
+                node = parent[node];
+            }
+            node
+        }
+        for &(n, s) in &self.edges {
+            let (a, b) = (root(&mut parent, n), root(&mut parent, self.numbers.len() + s));
+            parent[a.max(b)] = a.min(b);
+        }
+
+        let mut components: Vec<Component> = Vec::new();
+        let mut by_root: HashMap<usize, usize> = HashMap::new();
+        for node in 0..parent.len() {
+            let i = *by_root.entry(root(&mut parent, node)).or_insert_with(|| {
+                components.push(Component { numbers: Vec::new(), symbols: Vec::new() });
+                components.len() - 1
+            });
+            if node < self.numbers.len() {
+                components[i].numbers.push(node);
+            } else {
+                components[i].symbols.push(node - self.numbers.len());
+            }
+        }
+        components
+    }
+
+    // The symbol touching the most numbers and how many it touches. Ties go to
+    // the first in reading order.
+    pub fn busiest_symbol(&self) -> Option<(usize, usize)> {
+        (0..self.symbols.len())
+            .map(|s| (s, self.numbers_of(s).count()))
+            .fold(None, |best, (s, count)| match best {
+                Some((_, most)) if most >= count => best,
+                _ => Some((s, count))
+            })
+    }
+
+    // Numbers touching two or more symbols, with the symbols they touch.
+    pub fn shared_numbers(&self) -> Vec<(usize, Vec<usize>)> {
+        (0..self.numbers.len())
+            .map(|n| (n, self.symbols_of(n).collect::<Vec<usize>>()))
+            .filter(|(_, symbols)| symbols.len() > 1)
+            .collect()
+    }
+}
+
+pub fn write(out: &mut impl Write, format: Format, graph: &Graph) -> io::Result<()> {
+    match format {
+        Format::Dot => {
+            writeln!(out, "graph schematic {{")?;
+            for (i, part) in graph.numbers.iter().enumerate() {
+                writeln!(out, "  n{} [label=\"{}\", shape=box, tooltip=\"row {}, columns {}-{}\"];", i, part.value, part.row, part.start, part.end)?;
+            }
+            for (i, symbol) in graph.symbols.iter().enumerate() {
+                writeln!(
+                    out, "  s{} [label=\"{}\", shape=circle, tooltip=\"row {}, column {}\"];",
+                    i, escaped(symbol.symbol), symbol.at.row, symbol.at.col
+                )?;
+            }
+            for (n, s) in &graph.edges {
+                writeln!(out, "  n{} -- s{};", n, s)?;
+            }
+            writeln!(out, "}}")?;
+        },
+        Format::Json => {
+            let numbers: Vec<String> = graph.numbers.iter()
+                .map(|part| format!("{{\"value\": {}, \"row\": {}, \"start\": {}, \"end\": {}}}", part.value, part.row, part.start, part.end))
+                .collect();
+            let symbols: Vec<String> = graph.symbols.iter()
+                .map(|symbol| format!("{{\"symbol\": \"{}\", \"row\": {}, \"col\": {}}}", escaped(symbol.symbol), symbol.at.row, symbol.at.col))
+                .collect();
+            let edges: Vec<String> = graph.edges.iter().map(|(n, s)| format!("[{}, {}]", n, s)).collect();
+            writeln!(out, "{{")?;
+            writeln!(out, "  \"numbers\": [{}],", numbers.join(", "))?;
+            writeln!(out, "  \"symbols\": [{}],", symbols.join(", "))?;
+            writeln!(out, "  \"edges\": [{}]", edges.join(", "))?;
+            writeln!(out, "}}")?;
+        }
+    }
+    out.flush()
+}
+
+// Symbols are printable ASCII, so only quotes and backslashes need escaping,
+// the same way in DOT and JSON strings.
+fn escaped(symbol: u8) -> String {
+    match symbol {
+        b'"' => String::from("\\\""),
+        b'\\' => String::from("\\\\"),
+        c => String::from(c as char)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn graph(schematic: &str) -> Graph {
+        Graph::build(&Grid::from_str(schematic).expect("Expected a grid")).expect("Expected a graph")
+    }
+
+    #[test]
+    fn test_build() {
+        let graph = graph("467..114..\n...*......\n..35..633.\n......#...\n617*......");
+        assert_eq!(vec!{467, 114, 35, 633, 617}, graph.numbers.iter().map(|part| part.value).collect::<Vec<u64>>());
+        assert_eq!(vec!{Point::new(1, 3), Point::new(3, 6), Point::new(4, 3)}, graph.symbols.iter().map(|s| s.at).collect::<Vec<Point>>());
+        assert_eq!(vec!{(0, 0), (2, 0), (3, 1), (4, 2)}, graph.edges);
+    }
+
+    #[test]
+    fn test_queries() {
+        let graph = graph("1.2.3\n.*.*.\n4.5.6\n.....\n7#..8");
+        let components = graph.components();
+        assert_eq!(3, components.len());
+        assert_eq!(Component { numbers: vec!{0, 1, 2, 3, 4, 5}, symbols: vec!{0, 1} }, components[0]);
+        assert_eq!(Component { numbers: vec!{6}, symbols: vec!{2} }, components[1]);
+        assert_eq!(Component { numbers: vec!{7}, symbols: vec!{} }, components[2]);
+        assert_eq!(8, components[0].len());
+        // Both stars touch four numbers, so the first one wins
+        assert_eq!(Some((0, 4)), graph.busiest_symbol());
+        assert_eq!(vec!{(1, vec!{0, 1}), (4, vec!{0, 1})}, graph.shared_numbers());
+        assert_eq!(vec!{0, 1, 3, 4}, graph.numbers_of(0).collect::<Vec<usize>>());
+        assert_eq!(vec!{6}, graph.numbers_of(2).collect::<Vec<usize>>());
+        assert_eq!(vec!{1}, graph.symbols_of(2).collect::<Vec<usize>>());
+        assert_eq!(0, graph.symbols_of(7).count());
+
+        let empty = Graph::build(&Grid::from_str("12..").expect("Expected a grid")).expect("Expected a graph");
+        assert_eq!(None, empty.busiest_symbol());
+        assert!(empty.shared_numbers().is_empty());
+    }
+
+    #[test]
+    fn test_write() {
+        let graph = graph("12\"\n..\\");
+        let mut out: Vec<u8> = Vec::new();
+        write(&mut out, Format::Dot, &graph).expect("Expected to write");
+        assert_eq!(
+            "graph schematic {\n  n0 [label=\"12\", shape=box, tooltip=\"row 0, columns 0-1\"];\n  \
+             s0 [label=\"\\\"\", shape=circle, tooltip=\"row 0, column 2\"];\n  \
+             s1 [label=\"\\\\\", shape=circle, tooltip=\"row 1, column 2\"];\n  n0 -- s0;\n  n0 -- s1;\n}\n",
+            String::from_utf8(out).expect("Expected utf8")
+        );
+        let mut out: Vec<u8> = Vec::new();
+        write(&mut out, Format::Json, &graph).expect("Expected to write");
+        assert_eq!(
+            "{\n  \"numbers\": [{\"value\": 12, \"row\": 0, \"start\": 0, \"end\": 1}],\n  \
+             \"symbols\": [{\"symbol\": \"\\\"\", \"row\": 0, \"col\": 2}, {\"symbol\": \"\\\\\", \"row\": 1, \"col\": 2}],\n  \
+             \"edges\": [[0, 0], [0, 1]]\n}\n",
+            String::from_utf8(out).expect("Expected utf8")
+        );
+    }
+
+    #[test]
+    fn test_format_from_path() {
+        assert_eq!(Format::Json, Format::from_path(Path::new("schematic.json")));
+        assert_eq!(Format::Dot, Format::from_path(Path::new("schematic.dot")));
+        assert_eq!(Format::Dot, Format::from_path(Path::new("schematic")));
+    }
+}
@@ -0,0 +1,213 @@
+use std::fmt;
+use std::str::FromStr;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Point {
+    pub row: usize,
+    pub col: usize
+}
+
+impl Point {
+    pub fn new(row: usize, col: usize) -> Point {
+        Point { row, col }
+    }
+}
+
+#[allow(dead_code)]
+const FOUR: [(isize, isize); 4] = [(-1, 0), (0, -1), (0, 1), (1, 0)];
+const EIGHT: [(isize, isize); 8] = [(-1, -1), (-1, 0), (-1, 1), (0, -1), (0, 1), (1, -1), (1, 0), (1, 1)];
+
+// A rectangle of bytes, stored row after row. Every lookup is bounds
+// checked, so walking off an edge gives `None` rather than a panic.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Grid {
+    cells: Vec<u8>,
+    width: usize,
+    height: usize
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RaggedRow {
+    // Counted from 0, like every other row in the grid
+    pub row: usize,
+    pub expected: usize,
+    pub found: usize
+}
+
+impl fmt::Display for RaggedRow {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Row {} is {} bytes wide, expected {} like the first row", self.row + 1, self.found, self.expected)
+    }
+}
+
+impl Grid {
+    // Every row has to be as wide as the first one. Empty rows at the end,
+    // like the one a file ending in a blank line gives, are left out.
+    pub fn from_lines<I, S>(lines: I) -> Result<Grid, RaggedRow>
+        where I: IntoIterator<Item = S>,
+              S: AsRef<str> {
+        let mut grid = Grid::default();
+        let mut blank = 0;
+        for line in lines {
+            let row = line.as_ref().as_bytes();
+            if row.is_empty() {
+                blank += 1;
+                continue;
+            }
+            for _ in 0..blank {
+                grid.push_row(&[])?;
+            }
+            blank = 0;
+            grid.push_row(row)?;
+        }
+        Ok(grid)
+    }
+
+    pub fn push_row(&mut self, row: &[u8]) -> Result<(), RaggedRow> {
+        if self.height == 0 {
+            self.width = row.len();
+        } else if row.len() != self.width {
+            return Err(RaggedRow { row: self.height, expected: self.width, found: row.len() });
+        }
+        self.cells.extend_from_slice(row);
+        self.height += 1;
+        Ok(())
+    }
+
+    // Forgets the first row, so the grid can slide down a longer input.
+    pub fn drop_first_row(&mut self) {
+        if self.height > 0 {
+            self.cells.drain(..self.width);
+            self.height -= 1;
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn contains(&self, p: Point) -> bool {
+        p.row < self.height && p.col < self.width
+    }
+
+    pub fn get(&self, p: Point) -> Option<u8> {
+        if self.contains(p) { Some(self.cells[p.row * self.width + p.col]) } else { None }
+    }
+
+    pub fn row(&self, row: usize) -> Option<&[u8]> {
+        if row < self.height { Some(&self.cells[row * self.width..(row + 1) * self.width]) } else { None }
+    }
+
+    // Columns are not stored together, so they come out as an iterator. No
+    // puzzle reads one yet.
+    #[allow(dead_code)]
+    pub fn column(&self, col: usize) -> Option<impl Iterator<Item = u8> + '_> {
+        if col < self.width { Some(self.cells.iter().skip(col).step_by(self.width).copied()) } else { None }
+    }
+
+    pub fn rows(&self) -> impl Iterator<Item = &[u8]> {
+        (0..self.height).map(move |row| &self.cells[row * self.width..(row + 1) * self.width])
+    }
+
+    // Every point, row by row.
+    pub fn points(&self) -> impl Iterator<Item = Point> + '_ {
+        (0..self.height).flat_map(move |row| (0..self.width).map(move |col| Point { row, col }))
+    }
+
+    // `p` moved by the given number of rows and columns, if that stays on the grid.
+    pub fn offset(&self, p: Point, rows: isize, cols: isize) -> Option<Point> {
+        let moved = Point { row: p.row.checked_add_signed(rows)?, col: p.col.checked_add_signed(cols)? };
+        if self.contains(moved) { Some(moved) } else { None }
+    }
+
+    // Up, left, right and down, skipping any that fall off the grid. Symbols
+    // reach diagonally on this day, so only the tests walk these.
+    #[allow(dead_code)]
+    pub fn neighbours4(&self, p: Point) -> impl Iterator<Item = Point> + '_ {
+        FOUR.iter().filter_map(move |&(rows, cols)| self.offset(p, rows, cols))
+    }
+
+    // The four neighbours plus the diagonals, row by row.
+    pub fn neighbours8(&self, p: Point) -> impl Iterator<Item = Point> + '_ {
+        EIGHT.iter().filter_map(move |&(rows, cols)| self.offset(p, rows, cols))
+    }
+}
+
+impl FromStr for Grid {
+    type Err = RaggedRow;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Grid::from_lines(s.lines())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn grid() -> Grid {
+        Grid::from_str("abc\ndef\nghi\njkl").expect("Expected a grid")
+    }
+
+    #[test]
+    fn test_lookups() {
+        let grid = grid();
+        assert_eq!((3, 4), (grid.width(), grid.height()));
+        assert_eq!(Some(b'f'), grid.get(Point::new(1, 2)));
+        assert_eq!(None, grid.get(Point::new(1, 3)));
+        assert_eq!(None, grid.get(Point::new(4, 0)));
+        assert_eq!(Some(&b"ghi"[..]), grid.row(2));
+        assert_eq!(None, grid.row(4));
+        assert_eq!(Some(b"behk".to_vec()), grid.column(1).map(|c| c.collect()));
+        assert!(grid.column(3).is_none());
+        assert_eq!(vec!{&b"abc"[..], b"def", b"ghi", b"jkl"}, grid.rows().collect::<Vec<&[u8]>>());
+        assert_eq!(12, grid.points().count());
+    }
+
+    #[test]
+    fn test_push_and_drop_rows() {
+        let mut grid = Grid::default();
+        assert_eq!(Ok(()), grid.push_row(b".."));
+        assert_eq!(Ok(()), grid.push_row(b"#."));
+        assert_eq!(Ok(grid.clone()), Grid::from_str("..\n#."));
+        grid.drop_first_row();
+        assert_eq!(Ok(grid.clone()), Grid::from_str("#."));
+        grid.drop_first_row();
+        grid.drop_first_row();
+        assert_eq!(0, grid.height());
+        assert_eq!(Ok(()), grid.push_row(b"abc"));
+        assert_eq!(3, grid.width());
+    }
+
+    #[test]
+    fn test_neighbours() {
+        let grid = grid();
+        let middle: Vec<Point> = grid.neighbours8(Point::new(1, 1)).collect();
+        assert_eq!(8, middle.len());
+        assert_eq!(Point::new(0, 0), middle[0]);
+        assert_eq!(Point::new(2, 2), middle[7]);
+
+        let corner: Vec<Point> = grid.neighbours8(Point::new(0, 0)).collect();
+        assert_eq!(vec!{Point::new(0, 1), Point::new(1, 0), Point::new(1, 1)}, corner);
+        let corner: Vec<Point> = grid.neighbours4(Point::new(3, 2)).collect();
+        assert_eq!(vec!{Point::new(2, 2), Point::new(3, 1)}, corner);
+        assert_eq!(5, grid.neighbours8(Point::new(3, 1)).count());
+        assert_eq!(3, grid.neighbours4(Point::new(1, 0)).count());
+        assert_eq!(None, grid.offset(Point::new(0, 0), -1, 0));
+    }
+
+    #[test]
+    fn test_ragged_rows() {
+        assert_eq!(Err(RaggedRow { row: 1, expected: 3, found: 2 }), Grid::from_str("abc\nde\nfgh"));
+        assert_eq!("Row 2 is 2 bytes wide, expected 3 like the first row", RaggedRow { row: 1, expected: 3, found: 2 }.to_string());
+        assert_eq!(Grid::from_str("abc\ndef"), Grid::from_str("abc\ndef\n\n\n"));
+        assert_eq!(Err(RaggedRow { row: 1, expected: 3, found: 0 }), Grid::from_str("abc\n\ndef"));
+        let empty = Grid::from_str("").expect("Expected a grid");
+        assert_eq!((0, 0), (empty.width(), empty.height()));
+        assert_eq!(0, empty.points().count());
+    }
+}
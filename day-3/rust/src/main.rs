@@ -1,195 +1,324 @@
 use std::collections::HashSet;
+use std::env;
+use std::fmt;
 use std::fs::File;
-use std::io::{BufRead, BufReader, Error};
-use std::ops::Deref;
-use std::path::Path;
+use std::io::{self, BufRead, BufReader, BufWriter};
+use std::process::exit;
 use std::str::FromStr;
-use std::sync::mpsc::channel;
+use crate::graph::Graph;
+use crate::grid::{Grid, Point};
+use crate::rule::Rule;
+use crate::stream::Totals;
+
+mod cli;
+mod graph;
+mod grid;
+mod render;
+mod rule;
+mod stream;
 
 fn main() {
-    let input_file: File = File::open(Path::new("input.txt")).expect("Expected input.txt file");
-    let mut line_reader = BufReader::new(input_file).lines();
-    let all_lines: Vec<String> = line_reader.map( | l| {
-        l.expect("Could not process line")
-    }).collect();
+    let args: Vec<String> = env::args().skip(1).collect();
+    let options = match cli::parse(&args) {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("{}", e);
+            exit(2);
+        }
+    };
 
-    let running_total = process_all_lines(all_lines);
+    let reader: Box<dyn BufRead> = match &options.input {
+        Some(path) if path.as_os_str() == "-" => Box::new(BufReader::new(io::stdin())),
+        Some(path) => Box::new(BufReader::new(File::open(path).expect("Could not open the file"))),
+        None => Box::new(BufReader::new(File::open("input.txt").expect("Expected input.txt file")))
+    };
+    // The puzzle's own rule stands in when none are given
+    let rules = if options.rules.is_empty() { vec!{Rule::default()} } else { options.rules.clone() };
+    let totals = if options.whole_grid() {
+        let grid = load(reader);
+        render_grid(&grid, &rules[0], &options);
+        if options.graph.is_some() || options.graph_report {
+            graph_grid(&grid, &options);
+        }
+        Totals {
+            parts: or_exit(sum_part_numbers(&grid)),
+            outcomes: rules.iter().map(|rule| or_exit(rule.apply(&grid))).collect()
+        }
+    } else {
+        or_exit(stream::scan(reader, &rules))
+    };
 
-    println!("The ratio of the gears is {}", running_total);
+    if options.mode.parts() {
+        println!("The sum of the part numbers is {}", totals.parts);
+    }
+    if !options.mode.gears() {
+        return;
+    }
+    if options.rules.is_empty() {
+        println!("The ratio of the gears is {}", totals.outcomes[0].total);
+    }
+    for (rule, outcome) in options.rules.iter().zip(&totals.outcomes) {
+        println!("Rule {}: {} gears, total {}", rule.name, outcome.gears, outcome.total);
+    }
 }
 
-#[derive(PartialEq, Eq, Hash)]
-struct EnginePart {
-    start: usize,
-    end: usize,
-    value: u32
-}
-
-fn process_all_lines(all_lines: Vec<String>) -> u32 {
-    let mut all_lines_iter = all_lines.into_iter();
-    let mut previous_line: Option<String> = None;
-    let mut current_line: Option<String> = all_lines_iter.next();
-    let mut next_line: Option<String> = all_lines_iter.next();
-    let mut running_total = 0u32;
-
-    while current_line.is_some() {
-        running_total += get_part_sum(&previous_line, &current_line, &next_line);
-        previous_line = current_line.take();
-        current_line = next_line.take();
-
-        let possible_next = all_lines_iter.next();
-        if possible_next.is_some() {
-            next_line = possible_next;
-        } else {
-            next_line = None;
-        }
-    }
-    running_total
-}
-
-fn get_part_sum(previous_line: &Option<String>, current_line: &Option<String>, next_line: &Option<String>) -> u32 {
-    let mut gear_ratio = 0;
-    current_line.clone().expect("Current Line Expected to be some")
-        .chars()
-        .enumerate()
-        .for_each(|(index, c)| {
-            if c == '*' {
-                let mut adjacent_parts: HashSet<EnginePart> = visit_eight_neighbors(index, &previous_line, &current_line, &next_line);
-
-                if adjacent_parts.len() == 2 {
-                    gear_ratio = adjacent_parts
-                        .drain()
-                        .fold(1u32, |a, x| a * x.value)
-                }
-            }
-    });
-    gear_ratio
-}
-
-fn visit_eight_neighbors(index: usize, prev_line: &Option<String>, current_line: &Option<String>, next_line: &Option<String>) -> HashSet<EnginePart> {
-    let mut parts: HashSet<EnginePart> = HashSet::new();
-    // Previous Line
-    if prev_line.is_some() {
-        let prev_str = prev_line.clone().expect("Expected Previous Line");
-        let prev_chars: Vec<char> = prev_str.chars().collect();
-        if check_neighbor(index-1, &prev_chars) {
-            let opt_part = find_number_slice(index-1, &prev_str);
-            parts.insert(opt_part.expect("Expected a part"));
-        }
-        if check_neighbor(index, &prev_chars) {
-            let opt_part = find_number_slice(index, &prev_str);
-            parts.insert(opt_part.expect("Expected a part"));
-        }
-        if check_neighbor(index+1, &prev_chars) {
-            let opt_part = find_number_slice(index+1, &prev_str);
-            parts.insert(opt_part.expect("Expected a part"));
+fn or_exit<T, E: fmt::Display>(result: Result<T, E>) -> T {
+    match result {
+        Ok(value) => value,
+        Err(e) => {
+            eprintln!("{}", e);
+            exit(1);
         }
     }
-    // Current Line
+}
 
-    let current_str = current_line.clone().expect("Expected a string");
-    let current_chars: Vec<char> = current_str
-        .chars()
-        .collect();
+// Rendering needs the whole schematic, so only then is it read into memory.
+fn load(reader: impl BufRead) -> Grid {
+    let lines = reader.lines().map(|l| l.expect("Could not process line"));
+    or_exit(Grid::from_lines(lines))
+}
 
-    if check_neighbor(index-1, &current_chars) {
-        let opt_part = find_number_slice(index-1, &current_str);
-        parts.insert(opt_part.expect("Expected a part"));
+fn graph_grid(grid: &Grid, options: &cli::Options) {
+    let graph = or_exit(Graph::build(grid));
+    if let Some(path) = &options.graph {
+        let mut out = BufWriter::new(File::create(path).expect("Could not create the graph file"));
+        graph::write(&mut out, graph::Format::from_path(path), &graph).expect("Unable to write the graph");
     }
-    if check_neighbor(index+1, &current_chars) {
-        let opt_part = find_number_slice(index+1, &current_str);
-        parts.insert(opt_part.expect("Expected a part"));
+    if options.graph_report {
+        print_graph_report(&graph);
     }
+}
 
-    // Next Line
+fn print_graph_report(graph: &Graph) {
+    let components = graph.components();
+    println!("{} numbers, {} symbols and {} edges in {} components", graph.numbers.len(), graph.symbols.len(), graph.edges.len(), components.len());
+    if let Some(largest) = components.iter().rev().max_by_key(|c| c.len()) {
+        println!("The largest component has {} numbers and {} symbols", largest.numbers.len(), largest.symbols.len());
+    }
+    if let Some((s, count)) = graph.busiest_symbol() {
+        let symbol = graph.symbols[s];
+        println!("The busiest symbol is {} at row {}, column {}, touching {} numbers", symbol.symbol as char, symbol.at.row + 1, symbol.at.col + 1, count);
+    }
+    let shared = graph.shared_numbers();
+    println!("{} numbers touch more than one symbol", shared.len());
+    for (n, symbols) in shared {
+        let part = graph.numbers[n];
+        let symbols: Vec<String> = symbols.iter().map(|&s| (graph.symbols[s].symbol as char).to_string()).collect();
+        println!("  {} at row {}, column {} touches {}", part.value, part.row + 1, part.start + 1, symbols.join(" "));
+    }
+}
 
-    if next_line.is_some() {
-        let next_str = next_line.clone().expect("Expected next line");
-        let next_chars: Vec<char> = next_str.chars().collect();
-        if check_neighbor(index-1, &next_chars) {
-            let opt_part = find_number_slice(index-1, &next_str);
-            parts.insert(opt_part.expect("Expected a part"));
-        }
-        if check_neighbor(index, &next_chars) {
-            let opt_part = find_number_slice(index, &next_str);
-            parts.insert(opt_part.expect("Expected a part"));
-        }
-        if check_neighbor(index+1, &next_chars) {
-            let opt_part = find_number_slice(index+1, &next_str);
-            parts.insert(opt_part.expect("Expected a part"));
-        }
+// Gears are highlighted by the first rule given, or the puzzle's own.
+fn render_grid(grid: &Grid, rule: &Rule, options: &cli::Options) {
+    if !options.render && options.svg.is_none() {
+        return;
+    }
+    let annotations = or_exit(render::annotate(grid, rule));
+    if options.render {
+        render::write_ansi(&mut io::stdout().lock(), grid, &annotations).expect("Unable to print the schematic");
+    }
+    if let Some(path) = &options.svg {
+        let mut out = BufWriter::new(File::create(path).expect("Could not create the SVG file"));
+        render::write_svg(&mut out, grid, &annotations).expect("Unable to write the SVG");
     }
+}
 
-    return parts;
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+struct EnginePart {
+    row: usize,
+    start: usize,
+    end: usize,
+    value: u64
 }
 
-fn check_neighbor(index: usize, chars: &Vec<char>) -> bool {
-    let c = chars.get(index);
+// A number, or the part number sum, grew past what a u64 holds at the
+// number starting at `at`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct PartsOverflow {
+    at: Point,
+    what: &'static str
+}
+
+impl PartsOverflow {
+    fn number(at: Point) -> PartsOverflow {
+        PartsOverflow { at, what: "The number" }
+    }
+
+    fn sum(at: Point) -> PartsOverflow {
+        PartsOverflow { at, what: "The sum of the part numbers up to the number" }
+    }
+}
 
-    match c {
-        Some(c) => c.is_digit(10),
-        None => false
+impl fmt::Display for PartsOverflow {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at row {}, column {} does not fit in 64 bits", self.what, self.at.row + 1, self.at.col + 1)
     }
 }
 
-fn find_number_slice(index: usize, line: &str) -> Option<EnginePart>{
-    let mut start: usize = index;
-    let mut end: usize = index;
-    let chars: Vec<char> = line.chars().collect();
+fn sum_part_numbers(grid: &Grid) -> Result<u64, PartsOverflow> {
+    (0..grid.height()).flat_map(|row| part_numbers(grid, row)).try_fold(0u64, |sum, part| {
+        let part = part?;
+        sum.checked_add(part.value).ok_or(PartsOverflow::sum(Point::new(part.row, part.start)))
+    })
+}
+
+// Every number on `row`, left to right.
+fn numbers(grid: &Grid, row: usize) -> impl Iterator<Item = Result<EnginePart, PartsOverflow>> + '_ {
+    let cells = grid.row(row).unwrap_or(&[]);
+    (0..cells.len())
+        .filter(move |&col| cells[col].is_ascii_digit() && (col == 0 || !cells[col - 1].is_ascii_digit()))
+        .filter_map(move |col| find_number_slice(grid, Point::new(row, col)).transpose())
+}
+
+// The numbers on `row` that touch at least one symbol, left to right. Numbers
+// too big to read are passed on whatever they touch.
+fn part_numbers(grid: &Grid, row: usize) -> impl Iterator<Item = Result<EnginePart, PartsOverflow>> + '_ {
+    numbers(grid, row).filter(move |part| part.as_ref().map_or(true, |part| touches_symbol(grid, part)))
+}
 
-    if !chars[index].is_digit(10) {
-        return None;
+fn touches_symbol(grid: &Grid, part: &EnginePart) -> bool {
+    (part.start..=part.end).any(|col| grid.neighbours8(Point::new(part.row, col)).any(|n| grid.get(n).is_some_and(is_symbol)))
+}
+
+fn visit_eight_neighbors(grid: &Grid, p: Point) -> Result<HashSet<EnginePart>, PartsOverflow> {
+    grid.neighbours8(p).filter_map(|n| find_number_slice(grid, n).transpose()).collect()
+}
+
+// The whole number that the digit at `p` is part of.
+fn find_number_slice(grid: &Grid, p: Point) -> Result<Option<EnginePart>, PartsOverflow> {
+    let Some(row) = grid.row(p.row) else {
+        return Ok(None);
+    };
+    if !row.get(p.col).is_some_and(u8::is_ascii_digit) {
+        return Ok(None);
     }
 
-    loop {
-        let mut expanded: bool = false;
-        if start > 0 {
-            if chars[start - 1].is_digit(10) {
-                start -= 1;
-                expanded = true;
-            }
-        }
-        if end < chars.len()-1 {
-            if chars[end+1].is_digit(10) {
-                end += 1;
-                expanded = true;
-            }
-        }
-        if expanded {
-            expanded = false
-        } else {
-            break;
-        }
+    let mut start = p.col;
+    let mut end = p.col;
+    while start > 0 && row[start - 1].is_ascii_digit() {
+        start -= 1;
+    }
+    while end + 1 < row.len() && row[end + 1].is_ascii_digit() {
+        end += 1;
     }
 
-    let part_num = &line[start..end + 1];
-    let part = EnginePart{
-        start,
-        end,
-        value: u32::from_str(part_num).expect("Unable to parse line slice")
-    };
-    return Some(part);
+    let part_num = std::str::from_utf8(&row[start..end + 1]).expect("Digits are ASCII");
+    // Digits alone can only fail to parse by being too big
+    let value = u64::from_str(part_num).map_err(|_| PartsOverflow::number(Point::new(p.row, start)))?;
+    Ok(Some(EnginePart { row: p.row, start, end, value }))
 }
 
-fn is_symbol(c: char) -> bool{
-    return (c >= '!' && c < '.') || (c == '/') || (c >= ':' && c <= '@') || (c >= '[' && c <= '`') || (c >= '{' && c <= '~')
+fn is_symbol(c: u8) -> bool {
+    matches!(c, b'!'..=b'-' | b'/' | b':'..=b'@' | b'['..=b'`' | b'{'..=b'~')
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    fn sum_gear_ratios(grid: &Grid) -> u64 {
+        Rule::default().apply(grid).expect("Expected no overflow").total
+    }
+
+    const EXAMPLE: &str = "467..114..\n...*......\n..35..633.\n......#...\n617*......\n.....+.58.\n..592.....\n......755.\n...$.*....\n.664.598..\n";
+
+    #[test]
+    fn example_part_one() {
+        let grid = Grid::from_str(EXAMPLE).expect("Expected a grid");
+        assert_eq!(Ok(4361), sum_part_numbers(&grid));
+    }
+
+    #[test]
+    fn test_part_numbers() {
+        // Counted once despite two symbols, and the same number on another row counts again
+        let grid = Grid::from_str("12#..\n$....\n12..7").expect("Expected a grid");
+        assert_eq!(Ok(24), sum_part_numbers(&grid));
+        assert!(is_symbol(b'#') && is_symbol(b'/') && is_symbol(b'~'));
+        assert!(!is_symbol(b'.') && !is_symbol(b'7') && !is_symbol(b'a'));
+    }
+
     #[test]
     fn example_part_two() {
-        let example: String =
-            String::from("467..114..\n...*......\n..35..633.\n......#...\n617*......\n.....+.58.\n..592.....\n......755.\n...$.*....\n.664.598..\n");
+        let grid = Grid::from_str(EXAMPLE).expect("Expected a grid");
+        assert_eq!(467835, sum_gear_ratios(&grid));
+    }
 
-        let example_string: Vec<String> = example.split("\n").map( | s: &str| {
-            String::from(s)
-        }).collect();
+    #[test]
+    fn test_every_gear_on_a_line_counts() {
+        let grid = Grid::from_str("2*3.4*5\n.......").expect("Expected a grid");
+        assert_eq!(6 + 20, sum_gear_ratios(&grid));
+    }
 
+    #[test]
+    fn test_gear_between_identical_numbers() {
+        let grid = Grid::from_str(".12.\n..*.\n.12.").expect("Expected a grid");
+        assert_eq!(2, visit_eight_neighbors(&grid, Point::new(1, 2)).expect("Expected parts").len());
+        assert_eq!(144, sum_gear_ratios(&grid));
+        assert_eq!(Ok(24), sum_part_numbers(&grid));
 
-        let gear_ratio = process_all_lines(example_string);
-        assert_eq!(467835, gear_ratio);
+        // A third copy makes it touch three parts, so it is no longer a gear
+        let grid = Grid::from_str("7.7\n7*.\n...").expect("Expected a grid");
+        assert_eq!(0, sum_gear_ratios(&grid));
+        let grid = Grid::from_str("12.\n.*.\n12.\n.*.\n12.").expect("Expected a grid");
+        assert_eq!(288, sum_gear_ratios(&grid));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_gears_in_the_corners() {
+        let cases = [
+            ("*2\n3.", 6),
+            ("2*\n.3", 6),
+            ("3.\n*2", 6),
+            (".3\n2*", 6),
+            ("*2.3*\n4...5\n.....\n6...7\n*8.9*", 8 + 15 + 48 + 63),
+            ("*", 0),
+            ("*12", 0)
+        ];
+        for (schematic, expected) in cases {
+            let grid = Grid::from_str(schematic).expect("Expected a grid");
+            assert_eq!(expected, sum_gear_ratios(&grid), "{}", schematic);
+        }
+    }
+
+    #[test]
+    fn test_gears_on_the_edges() {
+        let cases = [
+            ("2*3\n...", 6),
+            ("...\n2*3", 6),
+            ("2..\n*..\n3..", 6),
+            ("..2\n..*\n..3", 6),
+            ("12*34\n.....", 408),
+            (".....\n..*..\n12.34", 408),
+            ("4\n*\n5", 20)
+        ];
+        for (schematic, expected) in cases {
+            let grid = Grid::from_str(schematic).expect("Expected a grid");
+            assert_eq!(expected, sum_gear_ratios(&grid), "{}", schematic);
+        }
+    }
+
+    #[test]
+    fn test_find_number_slice() {
+        let grid = Grid::from_str("..123.\n45....").expect("Expected a grid");
+        let part = find_number_slice(&grid, Point::new(0, 3)).expect("Expected a number").expect("Expected a part");
+        assert_eq!((0, 2, 4, 123), (part.row, part.start, part.end, part.value));
+        let part = find_number_slice(&grid, Point::new(1, 0)).expect("Expected a number").expect("Expected a part");
+        assert_eq!((1, 0, 1, 45), (part.row, part.start, part.end, part.value));
+        assert_eq!(Ok(None), find_number_slice(&grid, Point::new(0, 5)));
+        assert_eq!(Ok(None), find_number_slice(&grid, Point::new(2, 0)));
+    }
+
+    #[test]
+    fn test_long_numbers() {
+        let grid = Grid::from_str("12345678901*2\n.............").expect("Expected a grid");
+        assert_eq!(Ok(12345678901 + 2), sum_part_numbers(&grid));
+        assert_eq!(24691357802, sum_gear_ratios(&grid));
+
+        // One past u64::MAX
+        let grid = Grid::from_str("#18446744073709551616").expect("Expected a grid");
+        let e = sum_part_numbers(&grid).expect_err("Expected an overflow");
+        assert_eq!(PartsOverflow::number(Point::new(0, 1)), e);
+        assert_eq!("The number at row 1, column 2 does not fit in 64 bits", e.to_string());
+        assert_eq!(Err(e), find_number_slice(&grid, Point::new(0, 10)));
+    }
+}
@@ -0,0 +1,197 @@
+use std::io::{self, Write};
+use crate::grid::{Grid, Point};
+use crate::rule::Rule;
+use crate::{is_symbol, visit_eight_neighbors, PartsOverflow};
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Mark {
+    Plain,
+    // A digit of a number next to some symbol
+    Part,
+    // A digit of a number no symbol touches
+    Ignored,
+    Gear,
+    // Any other symbol, including gear symbols the rule turned down
+    Symbol
+}
+
+impl Mark {
+    fn ansi(&self) -> Option<&'static str> {
+        match self {
+            Mark::Plain => None,
+            Mark::Part => Some("\x1b[32m"),
+            Mark::Ignored => Some("\x1b[2;31m"),
+            Mark::Gear => Some("\x1b[1;33m"),
+            Mark::Symbol => Some("\x1b[36m")
+        }
+    }
+
+    // Text and background colours in the SVG
+    fn svg(&self) -> (&'static str, Option<&'static str>) {
+        match self {
+            Mark::Plain => ("#999999", None),
+            Mark::Part => ("#1b7f1b", Some("#d9f2d9")),
+            Mark::Ignored => ("#b22222", Some("#f8dcdc")),
+            Mark::Gear => ("#8a6d00", Some("#ffe680")),
+            Mark::Symbol => ("#00708a", None)
+        }
+    }
+}
+
+// A mark for every cell of a grid, row after row.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Annotations {
+    marks: Vec<Mark>,
+    width: usize
+}
+
+impl Annotations {
+    pub fn get(&self, p: Point) -> Mark {
+        self.marks[p.row * self.width + p.col]
+    }
+
+    // The cells of `row` grouped into runs of the same mark, as (start, end, mark).
+    fn runs(&self, row: usize) -> Vec<(usize, usize, Mark)> {
+        let mut runs: Vec<(usize, usize, Mark)> = Vec::new();
+        for col in 0..self.width {
+            let mark = self.get(Point::new(row, col));
+            match runs.last_mut() {
+                Some((_, end, last)) if *last == mark => *end = col,
+                _ => runs.push((col, col, mark))
+            }
+        }
+        runs
+    }
+}
+
+pub fn annotate(grid: &Grid, rule: &Rule) -> Result<Annotations, PartsOverflow> {
+    let mut marks: Vec<Mark> = grid.points().map(|p| match grid.get(p) {
+        Some(c) if c.is_ascii_digit() => Mark::Ignored,
+        Some(c) if is_symbol(c) => Mark::Symbol,
+        _ => Mark::Plain
+    }).collect();
+    let index = |row: usize, col: usize| row * grid.width() + col;
+    for p in grid.points().filter(|&p| grid.get(p).is_some_and(is_symbol)) {
+        let parts = visit_eight_neighbors(grid, p)?;
+        for part in &parts {
+            marks[index(part.row, part.start)..=index(part.row, part.end)].fill(Mark::Part);
+        }
+        if grid.get(p).is_some_and(|c| rule.is_gear(c)) && rule.count.accepts(parts.len()) {
+            marks[index(p.row, p.col)] = Mark::Gear;
+        }
+    }
+    Ok(Annotations { marks, width: grid.width() })
+}
+
+pub fn write_ansi(out: &mut impl Write, grid: &Grid, annotations: &Annotations) -> io::Result<()> {
+    for (row, cells) in grid.rows().enumerate() {
+        for (start, end, mark) in annotations.runs(row) {
+            let text = String::from_utf8_lossy(&cells[start..=end]);
+            match mark.ansi() {
+                Some(colour) => write!(out, "{}{}\x1b[0m", colour, text)?,
+                None => write!(out, "{}", text)?
+            }
+        }
+        writeln!(out)?;
+    }
+    out.flush()
+}
+
+const CELL_WIDTH: usize = 10;
+const CELL_HEIGHT: usize = 18;
+
+// Each row is one line of text stretched to exactly the width of its cells,
+// so the highlights behind it stay lined up with the characters.
+pub fn write_svg(out: &mut impl Write, grid: &Grid, annotations: &Annotations) -> io::Result<()> {
+    let (width, height) = (grid.width() * CELL_WIDTH, grid.height() * CELL_HEIGHT);
+    writeln!(out, "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\" viewBox=\"0 0 {} {}\">", width, height, width, height)?;
+    writeln!(out, "<rect width=\"100%\" height=\"100%\" fill=\"#ffffff\"/>")?;
+    for (row, cells) in grid.rows().enumerate() {
+        let runs = annotations.runs(row);
+        for &(start, end, mark) in &runs {
+            if let (_, Some(background)) = mark.svg() {
+                writeln!(
+                    out, "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"{}\"/>",
+                    start * CELL_WIDTH, row * CELL_HEIGHT, (end - start + 1) * CELL_WIDTH, CELL_HEIGHT, background
+                )?;
+            }
+        }
+        if cells.is_empty() {
+            continue;
+        }
+        write!(
+            out, "<text x=\"0\" y=\"{}\" font-family=\"monospace\" font-size=\"14\" textLength=\"{}\" lengthAdjust=\"spacing\" xml:space=\"preserve\">",
+            row * CELL_HEIGHT + 14, width
+        )?;
+        for (start, end, mark) in runs {
+            write!(out, "<tspan fill=\"{}\">{}</tspan>", mark.svg().0, xml_text(&cells[start..=end]))?;
+        }
+        writeln!(out, "</text>")?;
+    }
+    writeln!(out, "</svg>")?;
+    out.flush()
+}
+
+fn xml_text(cells: &[u8]) -> String {
+    let mut escaped = String::with_capacity(cells.len());
+    for c in String::from_utf8_lossy(cells).chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            c => escaped.push(c)
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn annotated(schematic: &str) -> (Grid, Annotations) {
+        let grid = Grid::from_str(schematic).expect("Expected a grid");
+        let annotations = annotate(&grid, &Rule::default()).expect("Expected annotations");
+        (grid, annotations)
+    }
+
+    #[test]
+    fn test_annotate() {
+        let (_, annotations) = annotated("467..114..\n...*......\n..35..633.\n......#...\n617*......");
+        assert_eq!(Mark::Part, annotations.get(Point::new(0, 0)));
+        assert_eq!(Mark::Ignored, annotations.get(Point::new(0, 5)));
+        assert_eq!(Mark::Gear, annotations.get(Point::new(1, 3)));
+        assert_eq!(Mark::Part, annotations.get(Point::new(2, 8)));
+        assert_eq!(Mark::Symbol, annotations.get(Point::new(3, 6)));
+        // Only touches 617, so it is not a gear
+        assert_eq!(Mark::Symbol, annotations.get(Point::new(4, 3)));
+        assert_eq!(Mark::Plain, annotations.get(Point::new(4, 9)));
+    }
+
+    #[test]
+    fn test_write_ansi() {
+        let (grid, annotations) = annotated("1*2.3\n.....");
+        let mut out: Vec<u8> = Vec::new();
+        write_ansi(&mut out, &grid, &annotations).expect("Expected to write");
+        assert_eq!(
+            "\x1b[32m1\x1b[0m\x1b[1;33m*\x1b[0m\x1b[32m2\x1b[0m.\x1b[2;31m3\x1b[0m\n.....\n",
+            String::from_utf8(out).expect("Expected utf8")
+        );
+    }
+
+    #[test]
+    fn test_write_svg() {
+        let (grid, annotations) = annotated("12&..\n..<3.");
+        let mut out: Vec<u8> = Vec::new();
+        write_svg(&mut out, &grid, &annotations).expect("Expected to write");
+        let svg = String::from_utf8(out).expect("Expected utf8");
+        assert!(svg.starts_with("<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"50\" height=\"36\""));
+        assert!(svg.contains("<rect x=\"0\" y=\"0\" width=\"20\" height=\"18\" fill=\"#d9f2d9\"/>"));
+        assert!(svg.contains("<tspan fill=\"#00708a\">&amp;</tspan>"));
+        assert!(svg.contains("<tspan fill=\"#00708a\">&lt;</tspan>"));
+        assert_eq!(2, svg.matches("<text ").count());
+        assert!(svg.ends_with("</svg>\n"));
+    }
+}
@@ -0,0 +1,243 @@
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+use crate::grid::{Grid, Point};
+use crate::{is_symbol, visit_eight_neighbors};
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Count {
+    Exactly(usize),
+    AtLeast(usize)
+}
+
+impl Count {
+    pub fn accepts(&self, parts: usize) -> bool {
+        match *self {
+            Count::Exactly(n) => parts == n,
+            Count::AtLeast(n) => parts >= n
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Combiner {
+    Product,
+    Sum,
+    Max
+}
+
+impl Combiner {
+    // `None` when the result does not fit in 64 bits
+    pub fn combine(&self, mut values: impl Iterator<Item = u64>) -> Option<u64> {
+        match self {
+            Combiner::Product => values.try_fold(1u64, |a, x| a.checked_mul(x)),
+            Combiner::Sum => values.try_fold(0u64, |a, x| a.checked_add(x)),
+            Combiner::Max => Some(values.max().unwrap_or(0))
+        }
+    }
+}
+
+impl FromStr for Combiner {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "product" => Ok(Combiner::Product),
+            "sum" => Ok(Combiner::Sum),
+            "max" => Ok(Combiner::Max),
+            _ => Err(format!("Unknown combiner {:?}, expected product, sum or max", s))
+        }
+    }
+}
+
+// Which symbols count as gears, how many parts they have to touch, and how
+// those parts make up the gear's value.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Rule {
+    pub name: String,
+    pub symbols: Vec<u8>,
+    pub count: Count,
+    pub combiner: Combiner
+}
+
+impl Default for Rule {
+    // The puzzle's own rule: a '*' next to exactly two parts, multiplied
+    fn default() -> Self {
+        Rule { name: String::from("gear"), symbols: vec!{b'*'}, count: Count::Exactly(2), combiner: Combiner::Product }
+    }
+}
+
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct Outcome {
+    pub gears: usize,
+    pub total: u64
+}
+
+impl Outcome {
+    // Counts one more gear, found by `rule` at `at`.
+    pub fn add(&mut self, rule: &Rule, at: Point, value: u64) -> Result<(), Overflow> {
+        self.gears += 1;
+        self.total = self.total.checked_add(value).ok_or_else(|| rule.overflow(at, "the total up to the gear"))?;
+        Ok(())
+    }
+}
+
+// A gear's value or the running total grew past what a u64 holds.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Overflow {
+    pub rule: String,
+    pub at: Point,
+    pub what: &'static str
+}
+
+impl fmt::Display for Overflow {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Rule {}: {} at row {}, column {} does not fit in 64 bits", self.rule, self.what, self.at.row + 1, self.at.col + 1)
+    }
+}
+
+impl Rule {
+    pub fn is_gear(&self, c: u8) -> bool {
+        self.symbols.contains(&c)
+    }
+
+    // The value of the gear at `p`, or `None` when `p` is not a gear.
+    pub fn gear(&self, grid: &Grid, p: Point) -> Result<Option<u64>, Overflow> {
+        if !grid.get(p).is_some_and(|c| self.is_gear(c)) {
+            return Ok(None);
+        }
+        let parts = visit_eight_neighbors(grid, p).map_err(|_| self.overflow(p, "a number next to the gear"))?;
+        if !self.count.accepts(parts.len()) {
+            return Ok(None);
+        }
+        match self.combiner.combine(parts.iter().map(|part| part.value)) {
+            Some(value) => Ok(Some(value)),
+            None => Err(self.overflow(p, "the gear"))
+        }
+    }
+
+    pub fn apply(&self, grid: &Grid) -> Result<Outcome, Overflow> {
+        let mut outcome = Outcome::default();
+        for p in grid.points() {
+            if let Some(value) = self.gear(grid, p)? {
+                outcome.add(self, p, value)?;
+            }
+        }
+        Ok(outcome)
+    }
+
+    fn overflow(&self, at: Point, what: &'static str) -> Overflow {
+        Overflow { rule: self.name.clone(), at, what }
+    }
+}
+
+// NAME SYMBOLS COUNT COMBINER, such as "gear * 2 product" or "hub *#& 3+ max".
+// A count ending in `+` means at least that many parts.
+impl FromStr for Rule {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let fields: Vec<&str> = s.split_whitespace().collect();
+        let [name, symbols, count, combiner] = fields.as_slice() else {
+            return Err(format!("Expected NAME SYMBOLS COUNT COMBINER, found {:?}", s));
+        };
+        if let Some(c) = symbols.bytes().find(|&c| !is_symbol(c)) {
+            return Err(format!("Rule {}: {:?} is not a symbol", name, c as char));
+        }
+        let (n, at_least) = match count.strip_suffix('+') {
+            Some(n) => (n, true),
+            None => (*count, false)
+        };
+        let n = match usize::from_str(n) {
+            Ok(n) if n > 0 => n,
+            _ => return Err(format!("Rule {}: expected a positive count like 2 or 2+, found {:?}", name, count))
+        };
+        Ok(Rule {
+            name: String::from(*name),
+            symbols: symbols.bytes().collect(),
+            count: if at_least { Count::AtLeast(n) } else { Count::Exactly(n) },
+            combiner: Combiner::from_str(combiner).map_err(|e| format!("Rule {}: {}", name, e))?
+        })
+    }
+}
+
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let count = match self.count {
+            Count::Exactly(n) => n.to_string(),
+            Count::AtLeast(n) => format!("{}+", n)
+        };
+        let combiner = match self.combiner {
+            Combiner::Product => "product",
+            Combiner::Sum => "sum",
+            Combiner::Max => "max"
+        };
+        write!(f, "{} {} {} {}", self.name, String::from_utf8_lossy(&self.symbols), count, combiner)
+    }
+}
+
+// One rule per line, skipping blank lines and lines starting with `//`.
+pub fn load(path: &Path) -> Result<Vec<Rule>, String> {
+    let content = fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    content.lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty() && !line.trim_start().starts_with("//"))
+        .map(|(i, line)| Rule::from_str(line).map_err(|e| format!("{} line {}, {}", path.display(), i + 1, e)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    fn rule(s: &str) -> Rule {
+        Rule::from_str(s).expect("Expected a rule")
+    }
+
+    #[test]
+    fn test_parse_rules() {
+        assert_eq!(Rule::default(), rule("gear * 2 product"));
+        let hub = rule("hub  *#&  3+ max");
+        assert_eq!(vec!{b'*', b'#', b'&'}, hub.symbols);
+        assert_eq!(Count::AtLeast(3), hub.count);
+        assert_eq!(Combiner::Max, hub.combiner);
+        assert_eq!("hub *#& 3+ max", hub.to_string());
+        assert!(Rule::from_str("gear * 2").is_err());
+        assert!(Rule::from_str("gear *. 2 product").is_err());
+        assert!(Rule::from_str("gear * 0 product").is_err());
+        assert!(Rule::from_str("gear * two product").is_err());
+        assert_eq!(Err(String::from("Rule gear: Unknown combiner \"mean\", expected product, sum or max")), Rule::from_str("gear * 2 mean"));
+    }
+
+    #[test]
+    fn test_apply_rules() {
+        let grid = Grid::from_str("2*3.4#5\n....6..\n1.....9").expect("Expected a grid");
+        assert_eq!(Ok(Outcome { gears: 1, total: 6 }), Rule::default().apply(&grid));
+        assert_eq!(Ok(Outcome { gears: 1, total: 15 }), rule("three # 3 sum").apply(&grid));
+        assert_eq!(Ok(Outcome { gears: 2, total: 9 }), rule("any *# 2+ max").apply(&grid));
+        assert_eq!(Ok(Outcome { gears: 0, total: 0 }), rule("none & 1+ sum").apply(&grid));
+    }
+
+    #[test]
+    fn test_overflow() {
+        let grid = Grid::from_str("4000000000*4000000000*4000000000\n.....4000000000.4000000000......").expect("Expected a grid");
+        let e = rule("big * 3+ product").apply(&grid).expect_err("Expected an overflow");
+        assert_eq!(Point::new(0, 10), e.at);
+        assert_eq!("Rule big: the gear at row 1, column 11 does not fit in 64 bits", e.to_string());
+        let grid = Grid::from_str("2*99999999999999999999").expect("Expected a grid");
+        let e = Rule::default().apply(&grid).expect_err("Expected an overflow");
+        assert_eq!("Rule gear: a number next to the gear at row 1, column 2 does not fit in 64 bits", e.to_string());
+    }
+
+    #[test]
+    fn test_load_rules() {
+        let path = env::temp_dir().join(format!("day-3-rules-{}.txt", std::process::id()));
+        fs::write(&path, "// the puzzle\ngear * 2 product\n\nhub #$ 1+ sum\n").expect("Expected to write the rules");
+        assert_eq!(Ok(vec!{Rule::default(), rule("hub #$ 1+ sum")}), load(&path));
+        fs::write(&path, "gear * 2 product\nhub #$ 1+\n").expect("Expected to write the rules");
+        assert!(load(&path).expect_err("Expected an error").contains("line 2"));
+        fs::remove_file(&path).expect("Expected to remove the rules");
+    }
+}
@@ -0,0 +1,211 @@
+use std::fmt;
+use std::io::{self, BufRead};
+use crate::grid::{Grid, Point, RaggedRow};
+use crate::rule::{Outcome, Overflow, Rule};
+use crate::{part_numbers, PartsOverflow};
+
+#[derive(Debug)]
+pub enum StreamError {
+    Io(io::Error),
+    Ragged(RaggedRow),
+    Parts(PartsOverflow),
+    Gears(Overflow)
+}
+
+impl fmt::Display for StreamError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StreamError::Io(e) => write!(f, "Could not read the schematic: {}", e),
+            StreamError::Ragged(e) => write!(f, "{}", e),
+            StreamError::Parts(e) => write!(f, "{}", e),
+            StreamError::Gears(e) => write!(f, "{}", e)
+        }
+    }
+}
+
+// The part number sum and one outcome per rule, in the order the rules were given.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Totals {
+    pub parts: u64,
+    pub outcomes: Vec<Outcome>
+}
+
+// Adds up a schematic one row at a time. Only the row being added up and
+// the rows either side of it are kept, so memory stays the same however
+// many rows there are.
+pub struct Scanner<'a> {
+    rules: &'a [Rule],
+    // Starts and ends with a blank row, so the first and last rows have
+    // neighbours like any other
+    window: Grid,
+    rows: usize,
+    // Rows added up so far, which is also the index of the window's middle row
+    added: usize,
+    // Empty rows not added yet, since they are only dropped at the end
+    blank: usize,
+    totals: Totals
+}
+
+impl<'a> Scanner<'a> {
+    pub fn new(rules: &'a [Rule]) -> Scanner<'a> {
+        Scanner { rules, window: Grid::default(), rows: 0, added: 0, blank: 0, totals: Totals { parts: 0, outcomes: vec!{Outcome::default(); rules.len()} } }
+    }
+
+    // Empty rows at the end are left out, like `Grid::from_lines` does.
+    pub fn push_row(&mut self, row: &[u8]) -> Result<(), StreamError> {
+        if row.is_empty() {
+            self.blank += 1;
+            return Ok(());
+        }
+        while self.blank > 0 {
+            self.blank -= 1;
+            self.add_row(&[])?;
+        }
+        self.add_row(row)
+    }
+
+    fn add_row(&mut self, row: &[u8]) -> Result<(), StreamError> {
+        if self.rows == 0 {
+            self.window.push_row(&vec!{b'.'; row.len()}).expect("Expected an empty window");
+        }
+        self.window.push_row(row).map_err(|e| StreamError::Ragged(RaggedRow { row: self.rows, ..e }))?;
+        self.rows += 1;
+        if self.window.height() == 3 {
+            self.add_middle_row()?;
+            self.window.drop_first_row();
+        }
+        Ok(())
+    }
+
+    pub fn finish(mut self) -> Result<Totals, StreamError> {
+        if self.rows > 0 {
+            let blank = vec!{b'.'; self.window.width()};
+            self.window.push_row(&blank).expect("Expected a row as wide as the window");
+            self.add_middle_row()?;
+        }
+        Ok(self.totals)
+    }
+
+    fn add_middle_row(&mut self) -> Result<(), StreamError> {
+        let row = self.added;
+        for part in part_numbers(&self.window, 1) {
+            let part = part.map_err(|e| StreamError::Parts(PartsOverflow { at: Point::new(row, e.at.col), ..e }))?;
+            let at = Point::new(row, part.start);
+            self.totals.parts = self.totals.parts.checked_add(part.value).ok_or(StreamError::Parts(PartsOverflow::sum(at)))?;
+        }
+        for col in 0..self.window.width() {
+            let at = Point::new(row, col);
+            for (rule, outcome) in self.rules.iter().zip(self.totals.outcomes.iter_mut()) {
+                let gear = rule.gear(&self.window, Point::new(1, col)).map_err(|e| StreamError::Gears(Overflow { at, ..e }))?;
+                if let Some(value) = gear {
+                    outcome.add(rule, at, value).map_err(StreamError::Gears)?;
+                }
+            }
+        }
+        self.added += 1;
+        Ok(())
+    }
+}
+
+// Reads rows up to each newline, dropping a trailing `\r` like `lines` does.
+pub fn scan(mut reader: impl BufRead, rules: &[Rule]) -> Result<Totals, StreamError> {
+    let mut scanner = Scanner::new(rules);
+    let mut line: Vec<u8> = Vec::new();
+    loop {
+        line.clear();
+        if reader.read_until(b'\n', &mut line).map_err(StreamError::Io)? == 0 {
+            break;
+        }
+        if line.last() == Some(&b'\n') {
+            line.pop();
+            if line.last() == Some(&b'\r') {
+                line.pop();
+            }
+        }
+        scanner.push_row(&line)?;
+    }
+    scanner.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+    use crate::sum_part_numbers;
+
+    const EXAMPLE: &str = "467..114..\n...*......\n..35..633.\n......#...\n617*......\n.....+.58.\n..592.....\n......755.\n...$.*....\n.664.598..\n";
+
+    fn rules() -> Vec<Rule> {
+        vec!{Rule::default(), Rule::from_str("hub *#$+ 1+ sum").expect("Expected a rule")}
+    }
+
+    #[test]
+    fn test_scan_example() {
+        let totals = scan(EXAMPLE.as_bytes(), &rules()).expect("Expected totals");
+        assert_eq!(4361, totals.parts);
+        assert_eq!(Outcome { gears: 2, total: 467835 }, totals.outcomes[0]);
+    }
+
+    #[test]
+    fn test_scan_matches_the_whole_grid() {
+        let schematics = [
+            EXAMPLE,
+            "*2\n3.",
+            ".3\n2*",
+            "2*3\n...\n4#5",
+            ".12.\n..*.\n.12.",
+            "12.\n.*.\n12.\n.*.\n12.",
+            "1\n*\n2\n$\n3",
+            "5*5",
+            "1*2\n...\n\n",
+            "12345678901*2\n..#9999999999"
+        ];
+        for schematic in schematics {
+            let grid = Grid::from_str(schematic).expect("Expected a grid");
+            let outcomes: Vec<Outcome> = rules().iter().map(|rule| rule.apply(&grid).expect("Expected an outcome")).collect();
+            let expected = Totals { parts: sum_part_numbers(&grid).expect("Expected a sum"), outcomes };
+            assert_eq!(expected, scan(schematic.as_bytes(), &rules()).expect("Expected totals"), "{}", schematic);
+        }
+    }
+
+    #[test]
+    fn test_line_endings() {
+        let totals = scan("1*2\r\n...\r\n".as_bytes(), &rules()).expect("Expected totals");
+        assert_eq!(3, totals.parts);
+        assert_eq!(2, totals.outcomes[0].total);
+        let totals = scan("1*2\n...\n\n\n".as_bytes(), &rules()).expect("Expected totals");
+        assert_eq!(3, totals.parts);
+        let totals = scan("".as_bytes(), &rules()).expect("Expected totals");
+        assert_eq!(Totals { parts: 0, outcomes: vec!{Outcome::default(); 2} }, totals);
+    }
+
+    #[test]
+    fn test_window_stays_small() {
+        let rules = rules();
+        let mut scanner = Scanner::new(&rules);
+        for i in 0..1000 {
+            let row = if i % 2 == 0 { "12*34....." } else { "...#567..." };
+            scanner.push_row(row.as_bytes()).expect("Expected a row");
+            assert!(scanner.window.height() <= 3);
+        }
+        let totals = scanner.finish().expect("Expected totals");
+        assert_eq!(500 * (12 + 34) + 500 * 567, totals.parts);
+    }
+
+    #[test]
+    fn test_errors_name_the_whole_schematic_row() {
+        let e = scan("...\n...\n...\n..".as_bytes(), &rules()).expect_err("Expected a ragged row");
+        assert_eq!("Row 4 is 2 bytes wide, expected 3 like the first row", e.to_string());
+        let e = scan("...\n\n...\n".as_bytes(), &rules()).expect_err("Expected a ragged row");
+        assert_eq!("Row 2 is 0 bytes wide, expected 3 like the first row", e.to_string());
+        let big = [Rule::from_str("big * 3+ product").expect("Expected a rule")];
+        let dots = ".".repeat(32);
+        let schematic = format!("{}\n{}\n4000000000*4000000000*4000000000\n.....4000000000.4000000000......", dots, dots);
+        let e = scan(schematic.as_bytes(), &big).expect_err("Expected an overflow");
+        assert_eq!("Rule big: the gear at row 3, column 11 does not fit in 64 bits", e.to_string());
+        let dots = ".".repeat(22);
+        let schematic = format!("{}\n{}\n.#18446744073709551616", dots, dots);
+        let e = scan(schematic.as_bytes(), &rules()).expect_err("Expected an overflow");
+        assert_eq!("The number at row 3, column 3 does not fit in 64 bits", e.to_string());
+    }
+}